@@ -0,0 +1,718 @@
+//! KServe v2 HTTP/REST frontend, including the binary tensor data extension.
+//! Requests are translated to `ModelInferRequest`s and served by the same
+//! `Server` as the gRPC frontend.
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use axum::{
+    body::Bytes,
+    extract::{FromRef, Path, State},
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use half::{bf16, f16};
+use infer_proto::infer_proto::infer_parameter::ParameterChoice;
+use infer_proto::infer_proto::model_infer_request::{InferInputTensor, InferRequestedOutputTensor};
+use infer_proto::infer_proto::{
+    InferParameter, InferTensorContents, ModelInferRequest, ModelInferResponse,
+};
+use log::error;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use tonic::{Code, Status};
+use triton_sys::wrapper::error::RustisError;
+use triton_sys::wrapper::server::Server;
+
+use crate::telemetry;
+use crate::timeout::{self, InferTimeouts};
+
+const HEADER_CONTENT_LENGTH: &str = "inference-header-content-length";
+
+pub fn router(infer_server: Arc<Server>, timeouts: Arc<InferTimeouts>) -> Router {
+    Router::new()
+        .route("/v2", get(server_metadata))
+        .route("/v2/health/live", get(server_live))
+        .route("/v2/health/ready", get(server_ready))
+        .route("/v2/models/:name", get(model_metadata))
+        .route("/v2/models/:name/versions/:version", get(model_metadata))
+        .route("/v2/models/:name/ready", get(model_ready))
+        .route("/v2/models/:name/versions/:version/ready", get(model_ready))
+        .route("/v2/models/:name/infer", post(model_infer))
+        .route(
+            "/v2/models/:name/versions/:version/infer",
+            post(model_infer),
+        )
+        .with_state(HttpState {
+            infer_server,
+            timeouts,
+        })
+}
+
+#[derive(Clone)]
+struct HttpState {
+    infer_server: Arc<Server>,
+    timeouts: Arc<InferTimeouts>,
+}
+
+impl FromRef<HttpState> for Arc<Server> {
+    fn from_ref(state: &HttpState) -> Self {
+        state.infer_server.clone()
+    }
+}
+
+impl FromRef<HttpState> for Arc<InferTimeouts> {
+    fn from_ref(state: &HttpState) -> Self {
+        state.timeouts.clone()
+    }
+}
+
+/// An error reported to the client as `{"error": "<message>"}`.
+#[derive(Debug)]
+pub struct HttpError {
+    status: StatusCode,
+    message: String,
+}
+
+impl HttpError {
+    fn bad_request(message: String) -> Self {
+        HttpError {
+            status: StatusCode::BAD_REQUEST,
+            message,
+        }
+    }
+}
+
+/// Maps the error to the HTTP status of the gRPC status it converts to, so
+/// both frontends report the same failures the same way.
+impl<E: Into<RustisError>> From<E> for HttpError {
+    fn from(e: E) -> Self {
+        let e = e.into();
+        let message = e.to_string();
+        let status = match Status::from(e).code() {
+            Code::InvalidArgument => StatusCode::BAD_REQUEST,
+            Code::NotFound => StatusCode::NOT_FOUND,
+            Code::AlreadyExists => StatusCode::CONFLICT,
+            Code::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+            Code::DeadlineExceeded => StatusCode::GATEWAY_TIMEOUT,
+            Code::Unimplemented => StatusCode::NOT_IMPLEMENTED,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        HttpError { status, message }
+    }
+}
+
+impl IntoResponse for HttpError {
+    fn into_response(self) -> Response {
+        error!("error in http request: {}", self.message);
+        (self.status, Json(json!({ "error": self.message }))).into_response()
+    }
+}
+
+#[derive(Deserialize)]
+struct ModelPath {
+    name: String,
+    #[serde(default)]
+    version: Option<String>,
+}
+
+impl ModelPath {
+    fn version(&self) -> Result<i64, HttpError> {
+        match &self.version {
+            Some(v) => v
+                .parse::<i64>()
+                .map_err(|e| HttpError::bad_request(format!("invalid model version: {}", e))),
+            None => Ok(-1),
+        }
+    }
+}
+
+fn health_status(ok: bool) -> StatusCode {
+    if ok {
+        StatusCode::OK
+    } else {
+        StatusCode::BAD_REQUEST
+    }
+}
+
+fn json_response(body: String) -> Response {
+    ([(header::CONTENT_TYPE, "application/json")], body).into_response()
+}
+
+async fn server_metadata(State(server): State<Arc<Server>>) -> Result<Response, HttpError> {
+    Ok(json_response(server.metadata()?))
+}
+
+async fn server_live(State(server): State<Arc<Server>>) -> Result<StatusCode, HttpError> {
+    Ok(health_status(server.is_live()?))
+}
+
+async fn server_ready(State(server): State<Arc<Server>>) -> Result<StatusCode, HttpError> {
+    Ok(health_status(server.is_ready()?))
+}
+
+async fn model_metadata(
+    State(server): State<Arc<Server>>,
+    Path(path): Path<ModelPath>,
+) -> Result<Response, HttpError> {
+    let metadata = server.model_metadata(&path.name, path.version()?)?;
+    Ok(json_response(metadata))
+}
+
+async fn model_ready(
+    State(server): State<Arc<Server>>,
+    Path(path): Path<ModelPath>,
+) -> Result<StatusCode, HttpError> {
+    Ok(health_status(
+        server.is_model_ready(&path.name, path.version()?)?,
+    ))
+}
+
+#[derive(Deserialize)]
+struct InferRequestBody {
+    #[serde(default)]
+    id: String,
+    #[serde(default)]
+    parameters: Map<String, Value>,
+    inputs: Vec<InputTensor>,
+    #[serde(default)]
+    outputs: Vec<RequestedOutput>,
+}
+
+#[derive(Deserialize)]
+struct InputTensor {
+    name: String,
+    shape: Vec<i64>,
+    datatype: String,
+    #[serde(default)]
+    parameters: Map<String, Value>,
+    #[serde(default)]
+    data: Option<Value>,
+}
+
+#[derive(Deserialize)]
+struct RequestedOutput {
+    name: String,
+    #[serde(default)]
+    parameters: Map<String, Value>,
+}
+
+#[derive(Serialize)]
+struct InferResponseBody {
+    model_name: String,
+    model_version: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    id: String,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    parameters: Map<String, Value>,
+    outputs: Vec<OutputTensor>,
+}
+
+#[derive(Serialize)]
+struct OutputTensor {
+    name: String,
+    datatype: String,
+    shape: Vec<i64>,
+    #[serde(skip_serializing_if = "Map::is_empty")]
+    parameters: Map<String, Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    data: Option<Vec<Value>>,
+}
+
+async fn model_infer(
+    State(server): State<Arc<Server>>,
+    State(timeouts): State<Arc<InferTimeouts>>,
+    Path(path): Path<ModelPath>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<Response, HttpError> {
+    let header_length = match headers.get(HEADER_CONTENT_LENGTH) {
+        Some(v) => v
+            .to_str()
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .ok_or_else(|| {
+                HttpError::bad_request(format!("invalid {} header", HEADER_CONTENT_LENGTH))
+            })?,
+        None => body.len(),
+    };
+    if header_length > body.len() {
+        return Err(HttpError::bad_request(format!(
+            "{} {} exceeds the request size {}",
+            HEADER_CONTENT_LENGTH,
+            header_length,
+            body.len()
+        )));
+    }
+    let request: InferRequestBody = serde_json::from_slice(&body[..header_length])
+        .map_err(|e| HttpError::bad_request(format!("failed to parse request: {}", e)))?;
+    let binary_output = bool_parameter(&request.parameters, "binary_data_output")?;
+    let requested_outputs = request
+        .outputs
+        .iter()
+        .map(|o| (o.name.clone(), o.parameters.clone()))
+        .collect::<HashMap<_, _>>();
+
+    let pb_request = pb_request(&path, request, &body[header_length..])?;
+    let timeout = timeouts.request_timeout(&pb_request, None)?;
+    let pb_response = timeout::with_timeout(
+        timeout,
+        server.infer(
+            Box::new(pb_request),
+            timeout.as_micros() as u64,
+            telemetry::http_trace_context(&headers),
+        ),
+    )
+    .await?;
+    http_response(pb_response, &requested_outputs, binary_output)
+}
+
+fn bool_parameter(parameters: &Map<String, Value>, name: &str) -> Result<bool, HttpError> {
+    match parameters.get(name) {
+        Some(Value::Bool(v)) => Ok(*v),
+        Some(_) => Err(HttpError::bad_request(format!(
+            "parameter '{}' must be a bool",
+            name
+        ))),
+        None => Ok(false),
+    }
+}
+
+/// Converts JSON parameters, dropping the ones only meaningful to the HTTP
+/// frontend.
+fn pb_parameters(
+    parameters: &Map<String, Value>,
+) -> Result<HashMap<String, InferParameter>, HttpError> {
+    let mut pb_parameters = HashMap::new();
+    for (name, value) in parameters {
+        if matches!(
+            name.as_str(),
+            "binary_data" | "binary_data_size" | "binary_data_output"
+        ) {
+            continue;
+        }
+        let choice = match value {
+            Value::Bool(v) => ParameterChoice::BoolParam(*v),
+            Value::String(v) => ParameterChoice::StringParam(v.clone()),
+            Value::Number(v) if v.is_i64() => ParameterChoice::Int64Param(v.as_i64().unwrap()),
+            _ => {
+                return Err(HttpError::bad_request(format!(
+                    "parameter '{}' must be a bool, an integer or a string",
+                    name
+                )))
+            }
+        };
+        pb_parameters.insert(
+            name.clone(),
+            InferParameter {
+                parameter_choice: Some(choice),
+            },
+        );
+    }
+    Ok(pb_parameters)
+}
+
+fn json_parameters(parameters: &HashMap<String, InferParameter>) -> Map<String, Value> {
+    parameters
+        .iter()
+        .filter_map(|(name, parameter)| {
+            let value = match parameter.parameter_choice.as_ref()? {
+                ParameterChoice::BoolParam(v) => Value::from(*v),
+                ParameterChoice::Int64Param(v) => Value::from(*v),
+                ParameterChoice::StringParam(v) => Value::from(v.clone()),
+            };
+            Some((name.clone(), value))
+        })
+        .collect()
+}
+
+/// Builds the protobuf request. Every input not in shared memory is sent
+/// as raw bytes, either taken from the binary section of the body or
+/// encoded from its JSON data.
+fn pb_request(
+    path: &ModelPath,
+    request: InferRequestBody,
+    mut binary: &[u8],
+) -> Result<ModelInferRequest, HttpError> {
+    let mut pb_request = ModelInferRequest {
+        model_name: path.name.clone(),
+        model_version: path.version.clone().unwrap_or_default(),
+        id: request.id,
+        parameters: pb_parameters(&request.parameters)?,
+        ..Default::default()
+    };
+    for input in request.inputs {
+        if let Some(size) = input.parameters.get("binary_data_size") {
+            let size = size.as_u64().ok_or_else(|| {
+                HttpError::bad_request(format!(
+                    "input '{}': binary_data_size must be an unsigned integer",
+                    input.name
+                ))
+            })? as usize;
+            if size > binary.len() {
+                return Err(HttpError::bad_request(format!(
+                    "input '{}': binary_data_size {} exceeds the remaining binary data {}",
+                    input.name,
+                    size,
+                    binary.len()
+                )));
+            }
+            let (data, rest) = binary.split_at(size);
+            pb_request.raw_input_contents.push(data.to_vec());
+            binary = rest;
+        } else if !input.parameters.contains_key("shared_memory_region") {
+            let data = input.data.as_ref().ok_or_else(|| {
+                HttpError::bad_request(format!("input '{}' has no data", input.name))
+            })?;
+            let mut elements = vec![];
+            flatten(data, &mut elements);
+            pb_request
+                .raw_input_contents
+                .push(encode_elements(&input.datatype, &elements)?);
+        }
+        pb_request.inputs.push(InferInputTensor {
+            name: input.name,
+            datatype: input.datatype,
+            shape: input.shape,
+            parameters: pb_parameters(&input.parameters)?,
+            contents: None,
+        });
+    }
+    if !binary.is_empty() {
+        return Err(HttpError::bad_request(format!(
+            "{} bytes of binary data were not used by any input",
+            binary.len()
+        )));
+    }
+    for output in request.outputs {
+        pb_request.outputs.push(InferRequestedOutputTensor {
+            parameters: pb_parameters(&output.parameters)?,
+            name: output.name,
+        });
+    }
+    Ok(pb_request)
+}
+
+fn http_response(
+    pb_response: ModelInferResponse,
+    requested_outputs: &HashMap<String, Map<String, Value>>,
+    binary_output: bool,
+) -> Result<Response, HttpError> {
+    let mut binary = vec![];
+    let mut outputs = Vec::with_capacity(pb_response.outputs.len());
+    for (i, output) in pb_response.outputs.into_iter().enumerate() {
+        let empty = Map::new();
+        let requested = requested_outputs.get(&output.name).unwrap_or(&empty);
+        let mut parameters = Map::new();
+        let mut data = None;
+
+        if requested.contains_key("shared_memory_region") {
+            // already written to the region by the allocator
+            for key in [
+                "shared_memory_region",
+                "shared_memory_offset",
+                "shared_memory_byte_size",
+            ] {
+                if let Some(v) = requested.get(key) {
+                    parameters.insert(key.to_owned(), v.clone());
+                }
+            }
+        } else {
+            let raw = pb_response.raw_output_contents.get(i);
+            let binary_data = match requested.get("binary_data") {
+                Some(_) => bool_parameter(requested, "binary_data")?,
+                None => binary_output,
+            };
+            if binary_data {
+                let bytes = match (raw, &output.contents) {
+                    (Some(raw), _) => raw.clone(),
+                    (None, Some(contents)) => encode_elements(
+                        &output.datatype,
+                        &contents_to_json(contents).iter().collect::<Vec<_>>(),
+                    )?,
+                    (None, None) => vec![],
+                };
+                parameters.insert("binary_data_size".to_owned(), Value::from(bytes.len()));
+                binary.extend(bytes);
+            } else {
+                data = Some(match (raw, &output.contents) {
+                    (Some(raw), _) => decode_raw(&output.datatype, raw)?,
+                    (None, Some(contents)) => contents_to_json(contents),
+                    (None, None) => vec![],
+                });
+            }
+        }
+
+        outputs.push(OutputTensor {
+            name: output.name,
+            datatype: output.datatype,
+            shape: output.shape,
+            parameters,
+            data,
+        });
+    }
+
+    let body = InferResponseBody {
+        model_name: pb_response.model_name,
+        model_version: pb_response.model_version,
+        id: pb_response.id,
+        parameters: json_parameters(&pb_response.parameters),
+        outputs,
+    };
+    let mut json = serde_json::to_vec(&body)?;
+    if binary.is_empty() {
+        return Ok(([(header::CONTENT_TYPE, "application/json")], json).into_response());
+    }
+    let header_length = HeaderValue::from(json.len());
+    json.extend(binary);
+    Ok((
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/octet-stream"),
+            ),
+            (
+                header::HeaderName::from_static(HEADER_CONTENT_LENGTH),
+                header_length,
+            ),
+        ],
+        json,
+    )
+        .into_response())
+}
+
+/// Collects the elements of a (possibly nested) JSON array in row-major order.
+fn flatten<'a>(value: &'a Value, elements: &mut Vec<&'a Value>) {
+    match value {
+        Value::Array(values) => values.iter().for_each(|v| flatten(v, elements)),
+        v => elements.push(v),
+    }
+}
+
+/// Encodes JSON elements in the little endian layout Triton expects for
+/// `datatype`, BYTES elements are prefixed with their 4 byte length.
+fn encode_elements(datatype: &str, elements: &[&Value]) -> Result<Vec<u8>, HttpError> {
+    let mut bytes = vec![];
+    for &v in elements {
+        let invalid = || HttpError::bad_request(format!("unable to parse '{}' as {}", v, datatype));
+        let int = |v: &Value| v.as_i64().ok_or_else(invalid);
+        let uint = |v: &Value| v.as_u64().ok_or_else(invalid);
+        match datatype {
+            "BOOL" => bytes.push(v.as_bool().ok_or_else(invalid)? as u8),
+            "UINT8" => bytes.extend(u8::try_from(uint(v)?).map_err(|_| invalid())?.to_le_bytes()),
+            "UINT16" => bytes.extend(
+                u16::try_from(uint(v)?)
+                    .map_err(|_| invalid())?
+                    .to_le_bytes(),
+            ),
+            "UINT32" => bytes.extend(
+                u32::try_from(uint(v)?)
+                    .map_err(|_| invalid())?
+                    .to_le_bytes(),
+            ),
+            "UINT64" => bytes.extend(uint(v)?.to_le_bytes()),
+            "INT8" => bytes.extend(i8::try_from(int(v)?).map_err(|_| invalid())?.to_le_bytes()),
+            "INT16" => bytes.extend(i16::try_from(int(v)?).map_err(|_| invalid())?.to_le_bytes()),
+            "INT32" => bytes.extend(i32::try_from(int(v)?).map_err(|_| invalid())?.to_le_bytes()),
+            "INT64" => bytes.extend(int(v)?.to_le_bytes()),
+            "FP16" => bytes.extend(f16::from_f64(v.as_f64().ok_or_else(invalid)?).to_le_bytes()),
+            "BF16" => bytes.extend(bf16::from_f64(v.as_f64().ok_or_else(invalid)?).to_le_bytes()),
+            "FP32" => bytes.extend((v.as_f64().ok_or_else(invalid)? as f32).to_le_bytes()),
+            "FP64" => bytes.extend(v.as_f64().ok_or_else(invalid)?.to_le_bytes()),
+            "BYTES" => {
+                let s = v.as_str().ok_or_else(invalid)?;
+                bytes.extend((s.len() as u32).to_le_bytes());
+                bytes.extend(s.as_bytes());
+            }
+            _ => {
+                return Err(HttpError::bad_request(format!(
+                    "datatype {} is not supported as JSON data, use binary data",
+                    datatype
+                )))
+            }
+        }
+    }
+    Ok(bytes)
+}
+
+fn decode_raw(datatype: &str, raw: &[u8]) -> Result<Vec<Value>, HttpError> {
+    fn chunks<const N: usize>(raw: &[u8]) -> impl Iterator<Item = [u8; N]> + '_ {
+        raw.chunks_exact(N).map(|c| c.try_into().unwrap())
+    }
+    let data = match datatype {
+        "BOOL" => raw.iter().map(|&v| Value::from(v != 0)).collect(),
+        "UINT8" => raw.iter().map(|&v| Value::from(v)).collect(),
+        "UINT16" => chunks(raw)
+            .map(|c| Value::from(u16::from_le_bytes(c)))
+            .collect(),
+        "UINT32" => chunks(raw)
+            .map(|c| Value::from(u32::from_le_bytes(c)))
+            .collect(),
+        "UINT64" => chunks(raw)
+            .map(|c| Value::from(u64::from_le_bytes(c)))
+            .collect(),
+        "INT8" => raw.iter().map(|&v| Value::from(v as i8)).collect(),
+        "INT16" => chunks(raw)
+            .map(|c| Value::from(i16::from_le_bytes(c)))
+            .collect(),
+        "INT32" => chunks(raw)
+            .map(|c| Value::from(i32::from_le_bytes(c)))
+            .collect(),
+        "INT64" => chunks(raw)
+            .map(|c| Value::from(i64::from_le_bytes(c)))
+            .collect(),
+        "FP16" => chunks(raw)
+            .map(|c| Value::from(f16::from_le_bytes(c).to_f32()))
+            .collect(),
+        "BF16" => chunks(raw)
+            .map(|c| Value::from(bf16::from_le_bytes(c).to_f32()))
+            .collect(),
+        "FP32" => chunks(raw)
+            .map(|c| Value::from(f32::from_le_bytes(c)))
+            .collect(),
+        "FP64" => chunks(raw)
+            .map(|c| Value::from(f64::from_le_bytes(c)))
+            .collect(),
+        "BYTES" => {
+            let mut data = vec![];
+            let mut rest = raw;
+            while !rest.is_empty() {
+                let invalid = || HttpError::bad_request("malformed BYTES output".to_owned());
+                let len: [u8; 4] = rest.get(..4).ok_or_else(invalid)?.try_into().unwrap();
+                let len = u32::from_le_bytes(len) as usize;
+                let element = rest.get(4..4 + len).ok_or_else(invalid)?;
+                data.push(Value::from(String::from_utf8_lossy(element).into_owned()));
+                rest = &rest[4 + len..];
+            }
+            data
+        }
+        _ => {
+            return Err(HttpError::bad_request(format!(
+                "datatype {} is not supported as JSON data, use binary data",
+                datatype
+            )))
+        }
+    };
+    Ok(data)
+}
+
+fn contents_to_json(contents: &InferTensorContents) -> Vec<Value> {
+    // only the field matching the tensor datatype is populated
+    contents
+        .bool_contents
+        .iter()
+        .map(|&v| Value::from(v))
+        .chain(contents.int_contents.iter().map(|&v| Value::from(v)))
+        .chain(contents.int64_contents.iter().map(|&v| Value::from(v)))
+        .chain(contents.uint_contents.iter().map(|&v| Value::from(v)))
+        .chain(contents.uint64_contents.iter().map(|&v| Value::from(v)))
+        .chain(contents.fp32_contents.iter().map(|&v| Value::from(v)))
+        .chain(contents.fp64_contents.iter().map(|&v| Value::from(v)))
+        .chain(
+            contents
+                .bytes_contents
+                .iter()
+                .map(|v| Value::from(String::from_utf8_lossy(v).into_owned())),
+        )
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_encode_decode_elements() {
+        let data = json!([[1, 2], [3, 4]]);
+        let mut elements = vec![];
+        flatten(&data, &mut elements);
+        let raw = encode_elements("INT16", &elements).unwrap();
+        assert_eq!(raw, vec![1, 0, 2, 0, 3, 0, 4, 0]);
+        assert_eq!(decode_raw("INT16", &raw).unwrap(), vec![1, 2, 3, 4]);
+
+        let data = json!(["ab", ""]);
+        let mut elements = vec![];
+        flatten(&data, &mut elements);
+        let raw = encode_elements("BYTES", &elements).unwrap();
+        assert_eq!(raw, vec![2, 0, 0, 0, b'a', b'b', 0, 0, 0, 0]);
+        assert_eq!(decode_raw("BYTES", &raw).unwrap(), vec!["ab", ""]);
+
+        assert!(encode_elements("UINT8", &[&json!(256)]).is_err());
+        let raw = encode_elements("FP16", &[&json!(1.5)]).unwrap();
+        assert_eq!(raw, vec![0x00, 0x3e]);
+        assert_eq!(decode_raw("FP16", &raw).unwrap(), vec![1.5]);
+        let raw = encode_elements("BF16", &[&json!(-2.0)]).unwrap();
+        assert_eq!(decode_raw("BF16", &raw).unwrap(), vec![-2.0]);
+        assert!(encode_elements("FP8", &[&json!(1.0)]).is_err());
+    }
+
+    #[test]
+    fn test_binary_request() {
+        let path = ModelPath {
+            name: "addsub".to_owned(),
+            version: None,
+        };
+        let request: InferRequestBody = serde_json::from_value(json!({
+            "inputs": [
+                {"name": "INPUT0", "shape": [2], "datatype": "FP32",
+                 "parameters": {"binary_data_size": 8}},
+                {"name": "INPUT1", "shape": [2], "datatype": "FP32", "data": [1.0, 2.0]}
+            ],
+            "outputs": [{"name": "OUTPUT0", "parameters": {"binary_data": true}}]
+        }))
+        .unwrap();
+        let binary = [0u8; 8];
+        let converted = pb_request(&path, request, &binary).unwrap();
+        assert_eq!(converted.raw_input_contents.len(), 2);
+        assert_eq!(converted.raw_input_contents[0], binary);
+        assert!(converted.inputs[0].parameters.is_empty());
+        assert!(converted.outputs[0].parameters.is_empty());
+
+        let request: InferRequestBody = serde_json::from_value(json!({
+            "inputs": [{"name": "INPUT0", "shape": [2], "datatype": "FP32",
+                        "parameters": {"binary_data_size": 16}}]
+        }))
+        .unwrap();
+        assert!(pb_request(&path, request, &binary).is_err());
+    }
+
+    #[test]
+    fn test_json_parameters() {
+        let parameters = Map::from_iter([
+            ("sequence_end".to_owned(), json!(true)),
+            ("score".to_owned(), json!(7)),
+            ("label".to_owned(), json!("cat")),
+        ]);
+        let pb_parameters = pb_parameters(&parameters).unwrap();
+        assert_eq!(json_parameters(&pb_parameters), parameters);
+    }
+
+    #[test]
+    fn test_error_status() {
+        let status = |e: RustisError| HttpError::from(e).status;
+        assert_eq!(
+            status(RustisError::Validation("invalid shape".to_owned())),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            status(RustisError::NotFound("unknown model".to_owned())),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            status(RustisError::Unsupported("decoupled model".to_owned())),
+            StatusCode::NOT_IMPLEMENTED
+        );
+        assert_eq!(status(RustisError::Timeout), StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            status(RustisError::Internal("allocation failed".to_owned())),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+        let error = HttpError::from(RustisError::Triton {
+            code: triton_sys::sys::TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE,
+            msg: "server is not ready".to_owned(),
+        });
+        assert_eq!(error.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(error.message, "server is not ready");
+    }
+}
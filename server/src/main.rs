@@ -1,41 +1,176 @@
 #![feature(vec_into_raw_parts)]
 use anyhow::Result;
 use log::{error, info, warn};
+use std::sync::Arc;
 // use infer_proto::infer_proto::{ServerLiveRequest, ServerLiveResponse};
 use infer_proto::infer_proto::grpc_inference_service_server::{
     GrpcInferenceService, GrpcInferenceServiceServer,
 };
+use infer_proto::infer_proto::model_repository_parameter::ParameterChoice;
+use infer_proto::infer_proto::system_shared_memory_status_response::RegionStatus;
 use infer_proto::infer_proto::{
-    ModelInferRequest, ModelInferResponse, ModelMetadataRequest, ModelMetadataResponse,
-    ModelReadyRequest, ModelReadyResponse, ServerLiveRequest, ServerLiveResponse,
-    ServerMetadataRequest, ServerMetadataResponse, ServerReadyRequest, ServerReadyResponse,
+    ModelConfigRequest, ModelConfigResponse, ModelInferRequest, ModelInferResponse,
+    ModelMetadataRequest, ModelMetadataResponse, ModelReadyRequest, ModelReadyResponse,
+    ModelRepositoryParameter, ModelStatisticsRequest, ModelStatisticsResponse,
+    ModelStreamInferResponse, RepositoryIndexRequest, RepositoryIndexResponse,
+    RepositoryModelLoadRequest, RepositoryModelLoadResponse, RepositoryModelUnloadRequest,
+    RepositoryModelUnloadResponse, ServerLiveRequest, ServerLiveResponse, ServerMetadataRequest,
+    ServerMetadataResponse, ServerReadyRequest, ServerReadyResponse,
+    SystemSharedMemoryRegisterRequest, SystemSharedMemoryRegisterResponse,
+    SystemSharedMemoryStatusRequest, SystemSharedMemoryStatusResponse,
+    SystemSharedMemoryUnregisterRequest, SystemSharedMemoryUnregisterResponse,
 };
-use tonic::{transport::Server, Request, Response, Status};
+use std::collections::HashMap;
+use tokio::sync::mpsc;
+use tokio_stream::wrappers::ReceiverStream;
+use tonic::{transport::Server, Request, Response, Status, Streaming};
+use triton_sys::wrapper::error::RustisError;
+use triton_sys::wrapper::parameter::Parameter;
+use triton_sys::wrapper::server_options::ServerOptionsBuilder;
+use triton_sys::wrapper::trace::{SpanExporter, TraceLevel, Tracer};
+
+mod http;
+mod metrics;
+mod telemetry;
+mod timeout;
+mod watcher;
+
+const STREAM_CHANNEL_CAPACITY: usize = 16;
+const DEFAULT_METRICS_PORT: u16 = 8002;
+// one request out of every 1000 is traced, as in Triton
+const DEFAULT_TRACE_RATE: u64 = 1000;
+const DEFAULT_SERVICE_NAME: &str = "rustis";
+const DEFAULT_MODEL_REPOSITORY: &str = "../../../models";
+const DEFAULT_WATCH_DEBOUNCE_MS: u64 = 1000;
 
-#[derive(Default)]
 pub struct RPCServer {
-    infer_server: triton_sys::wrapper::server::Server,
+    infer_server: Arc<triton_sys::wrapper::server::Server>,
+    timeouts: Arc<timeout::InferTimeouts>,
+}
+
+fn stream_response(
+    response: Result<ModelInferResponse, RustisError>,
+) -> Result<ModelStreamInferResponse, Status> {
+    match response {
+        Ok(r) => Ok(ModelStreamInferResponse {
+            error_message: String::new(),
+            infer_response: Some(r),
+        }),
+        Err(e) => {
+            error!("error in stream rpc: {:?}", e);
+            Ok(ModelStreamInferResponse {
+                error_message: format!("{}", e),
+                infer_response: None,
+            })
+        }
+    }
+}
+
+fn load_parameters(
+    parameters: HashMap<String, ModelRepositoryParameter>,
+) -> Result<Vec<Parameter>, Status> {
+    let mut load_parameters = Vec::with_capacity(parameters.len());
+    for (name, parameter) in parameters {
+        let parameter = match parameter.parameter_choice {
+            Some(ParameterChoice::BoolParam(v)) => Parameter::from_bool(&name, v),
+            Some(ParameterChoice::Int64Param(v)) => Parameter::from_int(&name, v),
+            Some(ParameterChoice::StringParam(v)) => Parameter::from_string(&name, &v),
+            Some(ParameterChoice::BytesParam(v)) => Parameter::from_bytes(&name, v),
+            None => {
+                return Err(Status::invalid_argument(format!(
+                    "parameter '{}' has no value",
+                    name
+                )))
+            }
+        };
+        load_parameters.push(parameter.map_err(Status::from)?);
+    }
+    Ok(load_parameters)
 }
 
 #[tonic::async_trait]
 impl GrpcInferenceService for RPCServer {
+    type ModelStreamInferStream = ReceiverStream<Result<ModelStreamInferResponse, Status>>;
+
     async fn model_infer(
         &self,
         request: Request<ModelInferRequest>,
     ) -> Result<Response<ModelInferResponse>, Status> {
-        let reply = self
-            .infer_server
-            .infer(Box::new(request.into_inner()), 5_000_000)
-            .await;
+        let deadline = timeout::grpc_deadline(request.metadata()).map_err(Status::from)?;
+        let trace_context = telemetry::grpc_trace_context(request.metadata());
+        let request = request.into_inner();
+        let timeout = self
+            .timeouts
+            .request_timeout(&request, deadline)
+            .map_err(Status::from)?;
+        let reply = timeout::with_timeout(
+            timeout,
+            self.infer_server
+                .infer(Box::new(request), timeout.as_micros() as u64, trace_context),
+        )
+        .await;
         match reply {
             Ok(r) => return Ok(Response::new(r)),
             Err(e) => {
                 error!("error in rpc: {:?}", e);
-                return Err(Status::internal(format!("error: {:?}", e)));
+                return Err(Status::from(e));
             }
         }
     }
 
+    async fn model_stream_infer(
+        &self,
+        request: Request<Streaming<ModelInferRequest>>,
+    ) -> Result<Response<Self::ModelStreamInferStream>, Status> {
+        // the deadline and the trace context cover the whole stream
+        let deadline = timeout::grpc_deadline(request.metadata()).map_err(Status::from)?;
+        let trace_context = telemetry::grpc_trace_context(request.metadata());
+        let mut in_stream = request.into_inner();
+        let (tx, rx) = mpsc::channel(STREAM_CHANNEL_CAPACITY);
+        let infer_server = self.infer_server.clone();
+        let timeouts = self.timeouts.clone();
+
+        tokio::spawn(async move {
+            loop {
+                let request = match in_stream.message().await {
+                    Ok(Some(r)) => r,
+                    Ok(None) => break,
+                    Err(e) => {
+                        error!("error reading stream rpc: {:?}", e);
+                        break;
+                    }
+                };
+                // requests are served concurrently, responses of one request stay in order
+                let infer_server = infer_server.clone();
+                let timeouts = timeouts.clone();
+                let tx = tx.clone();
+                tokio::spawn(async move {
+                    let reply = async {
+                        // only what the client or the model asked for, streams have no fallback
+                        let timeout = timeouts.requested_timeout(&request, deadline)?;
+                        let infer = infer_server.infer_stream(
+                            Box::new(request),
+                            timeout.map_or(0, |t| t.as_micros() as u64),
+                            trace_context,
+                            &tx,
+                            stream_response,
+                        );
+                        match timeout {
+                            Some(timeout) => timeout::with_timeout(timeout, infer).await,
+                            None => infer.await,
+                        }
+                    }
+                    .await;
+                    if let Err(e) = reply {
+                        let _ = tx.send(stream_response(Err(e))).await;
+                    }
+                });
+            }
+        });
+
+        Ok(Response::new(ReceiverStream::new(rx)))
+    }
+
     async fn server_live(
         &self,
         _request: Request<ServerLiveRequest>,
@@ -45,7 +180,7 @@ impl GrpcInferenceService for RPCServer {
             Ok(r) => return Ok(Response::new(ServerLiveResponse { live: r })),
             Err(e) => {
                 error!("error in rpc: {:?}", e);
-                return Err(Status::internal(format!("error: {:?}", e)));
+                return Err(Status::from(e));
             }
         }
     }
@@ -57,7 +192,7 @@ impl GrpcInferenceService for RPCServer {
         let reply = self.infer_server.is_ready();
         match reply {
             Ok(r) => return Ok(Response::new(ServerReadyResponse { ready: r })),
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => return Err(Status::from(e)),
         }
     }
 
@@ -68,7 +203,12 @@ impl GrpcInferenceService for RPCServer {
         let request = request.into_inner();
         let model_version = match request.version.parse::<i64>() {
             Ok(v) => v,
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => {
+                return Err(Status::invalid_argument(format!(
+                    "invalid model version: {}",
+                    e
+                )))
+            }
         };
 
         let reply = self
@@ -76,7 +216,7 @@ impl GrpcInferenceService for RPCServer {
             .is_model_ready(request.name.as_ref(), model_version);
         match reply {
             Ok(r) => return Ok(Response::new(ModelReadyResponse { ready: r })),
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => return Err(Status::from(e)),
         }
     }
 
@@ -95,7 +235,7 @@ impl GrpcInferenceService for RPCServer {
                 };
                 return Ok(Response::new(obj));
             }
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => return Err(Status::from(e)),
         }
     }
 
@@ -107,7 +247,12 @@ impl GrpcInferenceService for RPCServer {
         let model_name = request.name;
         let model_version = match request.version.parse::<i64>() {
             Ok(v) => v,
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => {
+                return Err(Status::invalid_argument(format!(
+                    "invalid model version: {}",
+                    e
+                )))
+            }
         };
         let reply = self.infer_server.model_metadata(&model_name, model_version);
         match reply {
@@ -120,9 +265,203 @@ impl GrpcInferenceService for RPCServer {
                 };
                 return Ok(Response::new(obj));
             }
-            Err(e) => return Err(Status::internal(format!("error: {:?}", e))),
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn model_statistics(
+        &self,
+        request: Request<ModelStatisticsRequest>,
+    ) -> Result<Response<ModelStatisticsResponse>, Status> {
+        let request = request.into_inner();
+        let model_version = if request.version.is_empty() {
+            -1
+        } else {
+            match request.version.parse::<i64>() {
+                Ok(v) => v,
+                Err(e) => {
+                    return Err(Status::invalid_argument(format!(
+                        "invalid model version: {}",
+                        e
+                    )))
+                }
+            }
+        };
+        let reply = self
+            .infer_server
+            .model_statistics(&request.name, model_version);
+        match reply {
+            Ok(r) => {
+                let model_stats = r.into_iter().map(Into::into).collect();
+                return Ok(Response::new(ModelStatisticsResponse { model_stats }));
+            }
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn model_config(
+        &self,
+        request: Request<ModelConfigRequest>,
+    ) -> Result<Response<ModelConfigResponse>, Status> {
+        let request = request.into_inner();
+        let model_version = if request.version.is_empty() {
+            -1
+        } else {
+            match request.version.parse::<i64>() {
+                Ok(v) => v,
+                Err(e) => {
+                    return Err(Status::invalid_argument(format!(
+                        "invalid model version: {}",
+                        e
+                    )))
+                }
+            }
+        };
+        let reply = self
+            .infer_server
+            .model_config(&request.name, model_version, 1);
+        match reply {
+            Ok(r) => {
+                return Ok(Response::new(ModelConfigResponse {
+                    config: Some(r.into()),
+                }))
+            }
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn repository_index(
+        &self,
+        request: Request<RepositoryIndexRequest>,
+    ) -> Result<Response<RepositoryIndexResponse>, Status> {
+        let request = request.into_inner();
+        if !request.repository_name.is_empty() {
+            return Err(Status::unimplemented(
+                "'repository_name' specification is not supported",
+            ));
+        }
+        let reply = self.infer_server.model_index(request.ready);
+        match reply {
+            Ok(r) => {
+                let models = r.into_iter().map(Into::into).collect();
+                return Ok(Response::new(RepositoryIndexResponse { models }));
+            }
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn repository_model_load(
+        &self,
+        request: Request<RepositoryModelLoadRequest>,
+    ) -> Result<Response<RepositoryModelLoadResponse>, Status> {
+        let request = request.into_inner();
+        if !request.repository_name.is_empty() {
+            return Err(Status::unimplemented(
+                "'repository_name' specification is not supported",
+            ));
+        }
+        let parameters = load_parameters(request.parameters)?;
+        let infer_server = self.infer_server.clone();
+        // loading blocks until the model is ready, keep it off the async workers
+        let reply = tokio::task::spawn_blocking(move || {
+            if parameters.is_empty() {
+                infer_server.load_model(&request.model_name)
+            } else {
+                infer_server.load_model_with_parameters(&request.model_name, &parameters)
+            }
+        })
+        .await;
+        match reply {
+            Ok(Ok(_)) => return Ok(Response::new(RepositoryModelLoadResponse {})),
+            Ok(Err(e)) => return Err(Status::from(e)),
+            Err(e) => return Err(Status::internal(e.to_string())),
         }
     }
+
+    async fn repository_model_unload(
+        &self,
+        request: Request<RepositoryModelUnloadRequest>,
+    ) -> Result<Response<RepositoryModelUnloadResponse>, Status> {
+        let request = request.into_inner();
+        if !request.repository_name.is_empty() {
+            return Err(Status::unimplemented(
+                "'repository_name' specification is not supported",
+            ));
+        }
+        let unload_dependents = match request.parameters.get("unload_dependents") {
+            Some(ModelRepositoryParameter {
+                parameter_choice: Some(ParameterChoice::BoolParam(v)),
+            }) => *v,
+            Some(_) => {
+                return Err(Status::invalid_argument(
+                    "parameter 'unload_dependents' must be a bool",
+                ))
+            }
+            None => false,
+        };
+        let reply = if unload_dependents {
+            self.infer_server
+                .unload_model_and_dependents(&request.model_name)
+        } else {
+            self.infer_server.unload_model(&request.model_name)
+        };
+        match reply {
+            Ok(_) => return Ok(Response::new(RepositoryModelUnloadResponse {})),
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn system_shared_memory_status(
+        &self,
+        request: Request<SystemSharedMemoryStatusRequest>,
+    ) -> Result<Response<SystemSharedMemoryStatusResponse>, Status> {
+        let request = request.into_inner();
+        let reply = self.infer_server.shared_memory.status(&request.name);
+        match reply {
+            Ok(r) => {
+                let regions = r
+                    .iter()
+                    .map(|region| {
+                        let status = RegionStatus {
+                            name: region.name().to_owned(),
+                            key: region.key().to_owned(),
+                            offset: region.offset() as u64,
+                            byte_size: region.byte_size() as u64,
+                        };
+                        (region.name().to_owned(), status)
+                    })
+                    .collect();
+                return Ok(Response::new(SystemSharedMemoryStatusResponse { regions }));
+            }
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn system_shared_memory_register(
+        &self,
+        request: Request<SystemSharedMemoryRegisterRequest>,
+    ) -> Result<Response<SystemSharedMemoryRegisterResponse>, Status> {
+        let request = request.into_inner();
+        let reply = self.infer_server.shared_memory.register(
+            &request.name,
+            &request.key,
+            request.offset as usize,
+            request.byte_size as usize,
+        );
+        match reply {
+            Ok(_) => return Ok(Response::new(SystemSharedMemoryRegisterResponse {})),
+            Err(e) => return Err(Status::from(e)),
+        }
+    }
+
+    async fn system_shared_memory_unregister(
+        &self,
+        request: Request<SystemSharedMemoryUnregisterRequest>,
+    ) -> Result<Response<SystemSharedMemoryUnregisterResponse>, Status> {
+        let request = request.into_inner();
+        self.infer_server.shared_memory.unregister(&request.name);
+        Ok(Response::new(SystemSharedMemoryUnregisterResponse {}))
+    }
 }
 
 #[tokio::main]
@@ -131,14 +470,122 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         warn!("init logger failed with error: {}", e);
     }
     let addr = "127.0.0.1:50051".parse().unwrap();
-    let rpc_server = RPCServer::default();
+    let http_addr = "127.0.0.1:8000".parse().unwrap();
+    let metrics_port = match std::env::var("RUSTIS_METRICS_PORT") {
+        Ok(port) => port.parse::<u16>()?,
+        Err(_) => DEFAULT_METRICS_PORT,
+    };
+    let metrics_addr = std::net::SocketAddr::from(([127, 0, 0, 1], metrics_port));
+    let timeouts = match std::env::var("RUSTIS_MODEL_TIMEOUTS") {
+        Ok(spec) => timeout::InferTimeouts::parse(&spec)?,
+        Err(_) => timeout::InferTimeouts::default(),
+    };
+    let timeouts = Arc::new(timeouts);
+
+    let frontend_metrics = Arc::new(metrics::FrontendMetrics::new()?);
+    let repository = std::env::var("RUSTIS_MODEL_REPOSITORY")
+        .unwrap_or_else(|_| DEFAULT_MODEL_REPOSITORY.to_owned());
+    let watch_mode = match std::env::var("RUSTIS_REPOSITORY_WATCH") {
+        Ok(mode) => Some(mode.parse::<watcher::WatchMode>()?),
+        Err(_) => None,
+    };
+    let mut options = ServerOptionsBuilder::new().model_repository_path(&repository);
+    if let Some(mode) = watch_mode {
+        options = options.model_control_mode(mode.control_mode());
+        if mode == watcher::WatchMode::Explicit {
+            options = options.startup_model("*");
+        }
+    }
+    let mut infer_server = triton_sys::wrapper::server::Server::new(options.build()?)?;
+    let conversion_metrics = frontend_metrics.clone();
+    infer_server.set_conversion_observer(Box::new(move |conversion, duration| {
+        conversion_metrics.observe_conversion(conversion, duration)
+    }));
+    if let Ok(policy) = std::env::var("RUSTIS_UNKNOWN_PARAMETERS") {
+        infer_server.set_unknown_parameters(policy.parse()?);
+    }
+    if let Ok(policy) = std::env::var("RUSTIS_DECOUPLED_MODELS") {
+        infer_server.set_decoupled_models(policy.parse()?);
+    }
+    let service_name =
+        std::env::var("OTEL_SERVICE_NAME").unwrap_or_else(|_| DEFAULT_SERVICE_NAME.to_owned());
+    let exporter: Option<Arc<dyn SpanExporter>> = match (
+        std::env::var("RUSTIS_OTLP_ENDPOINT"),
+        std::env::var("RUSTIS_TRACE_FILE"),
+    ) {
+        (Ok(endpoint), _) => Some(Arc::new(telemetry::OtlpExporter::new(
+            &endpoint,
+            &service_name,
+        )?)),
+        (_, Ok(path)) => Some(Arc::new(telemetry::FileExporter::new(path, &service_name)?)),
+        _ => None,
+    };
+    if let Some(exporter) = exporter.clone() {
+        let level = match std::env::var("RUSTIS_TRACE_LEVEL") {
+            Ok(level) => level.parse()?,
+            Err(_) => TraceLevel::Timestamps,
+        };
+        let rate = match std::env::var("RUSTIS_TRACE_RATE") {
+            Ok(rate) => rate.parse::<u64>()?,
+            Err(_) => DEFAULT_TRACE_RATE,
+        };
+        infer_server.set_tracer(Tracer::new(level, rate, exporter));
+    }
+    let infer_server = Arc::new(infer_server);
+    if let Some(mode) = watch_mode {
+        let debounce = match std::env::var("RUSTIS_REPOSITORY_WATCH_DEBOUNCE_MS") {
+            Ok(ms) => ms.parse::<u64>()?,
+            Err(_) => DEFAULT_WATCH_DEBOUNCE_MS,
+        };
+        watcher::RepositoryWatcher::new(
+            infer_server.clone(),
+            vec![std::fs::canonicalize(&repository)?],
+            mode,
+            std::time::Duration::from_millis(debounce),
+        )
+        .spawn()?;
+        info!(
+            "watching model repository {} in {:?} mode",
+            repository, mode
+        );
+    }
+    let rpc_server = RPCServer {
+        infer_server: infer_server.clone(),
+        timeouts: timeouts.clone(),
+    };
 
     info!("RPCServer listening on {}", addr);
+    info!("HTTP server listening on {}", http_addr);
+    info!("metrics server listening on {}", metrics_addr);
 
-    Server::builder()
+    let grpc = Server::builder()
+        .layer(metrics::GrpcMetricsLayer::new(frontend_metrics.clone()))
         .add_service(GrpcInferenceServiceServer::new(rpc_server))
-        .serve(addr)
-        .await?;
+        .serve(addr);
+    let http = axum::Server::bind(&http_addr)
+        .serve(http::router(infer_server.clone(), timeouts).into_make_service());
+    let metrics = axum::Server::bind(&metrics_addr)
+        .serve(metrics::router(infer_server, frontend_metrics).into_make_service());
+    let servers = async {
+        tokio::try_join!(
+            async { grpc.await.map_err(anyhow::Error::from) },
+            async { http.await.map_err(anyhow::Error::from) },
+            async { metrics.await.map_err(anyhow::Error::from) },
+        )
+    };
+    tokio::select! {
+        served = servers => {
+            served?;
+        }
+        signal = tokio::signal::ctrl_c() => {
+            signal?;
+            info!("shutting down");
+        }
+    }
+    // send the spans still queued before the process exits
+    if let Some(exporter) = exporter {
+        tokio::task::spawn_blocking(move || exporter.flush()).await?;
+    }
 
     Ok(())
 }
@@ -0,0 +1,261 @@
+//! Prometheus endpoint serving Triton's metrics together with the metrics
+//! of the gRPC frontend.
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use axum::{
+    extract::State,
+    http::{self, header, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use log::error;
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, Opts, Registry, TextEncoder,
+};
+use tower::{Layer, Service};
+use triton_sys::wrapper::metrics::Conversion;
+use triton_sys::wrapper::server::Server;
+
+pub struct FrontendMetrics {
+    registry: Registry,
+    grpc_requests: IntCounterVec,
+    grpc_request_duration: HistogramVec,
+    conversion_duration: HistogramVec,
+}
+
+impl FrontendMetrics {
+    pub fn new() -> prometheus::Result<Self> {
+        let registry = Registry::new();
+        let grpc_requests = IntCounterVec::new(
+            Opts::new(
+                "rustis_grpc_requests_total",
+                "Number of gRPC requests handled, by method and status code",
+            ),
+            &["method", "code"],
+        )?;
+        let grpc_request_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "rustis_grpc_request_duration_seconds",
+                "Time until the gRPC response headers are sent, by method",
+            ),
+            &["method"],
+        )?;
+        let conversion_duration = HistogramVec::new(
+            HistogramOpts::new(
+                "rustis_protobuf_conversion_duration_seconds",
+                "Time spent converting between protobuf messages and Triton requests and responses",
+            )
+            .buckets(prometheus::exponential_buckets(0.000_01, 4.0, 10)?),
+            &["conversion"],
+        )?;
+        registry.register(Box::new(grpc_requests.clone()))?;
+        registry.register(Box::new(grpc_request_duration.clone()))?;
+        registry.register(Box::new(conversion_duration.clone()))?;
+        Ok(FrontendMetrics {
+            registry,
+            grpc_requests,
+            grpc_request_duration,
+            conversion_duration,
+        })
+    }
+
+    pub fn observe_grpc(&self, method: &str, code: &str, duration: Duration) {
+        self.grpc_requests.with_label_values(&[method, code]).inc();
+        self.grpc_request_duration
+            .with_label_values(&[method])
+            .observe(duration.as_secs_f64());
+    }
+
+    pub fn observe_conversion(&self, conversion: Conversion, duration: Duration) {
+        self.conversion_duration
+            .with_label_values(&[conversion.as_str()])
+            .observe(duration.as_secs_f64());
+    }
+
+    fn formatted(&self) -> prometheus::Result<String> {
+        let mut buffer = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+pub fn router(infer_server: Arc<Server>, metrics: Arc<FrontendMetrics>) -> Router {
+    Router::new()
+        .route("/metrics", get(scrape))
+        .with_state((infer_server, metrics))
+}
+
+async fn scrape(
+    State((infer_server, metrics)): State<(Arc<Server>, Arc<FrontendMetrics>)>,
+) -> Response {
+    let triton = infer_server.metrics().and_then(|m| m.formatted());
+    match exposition(triton, &metrics) {
+        Ok(body) => ([(header::CONTENT_TYPE, prometheus::TEXT_FORMAT)], body).into_response(),
+        Err(e) => {
+            error!("failed to collect frontend metrics: {:?}", e);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
+
+/// Appends the frontend metrics to Triton's. Triton's are left out when they
+/// can't be collected, the frontend's are still served.
+fn exposition<E: fmt::Debug>(
+    triton: Result<String, E>,
+    metrics: &FrontendMetrics,
+) -> prometheus::Result<String> {
+    let frontend = metrics.formatted()?;
+    match triton {
+        Ok(triton) => Ok(triton + &frontend),
+        Err(e) => {
+            error!("failed to collect triton metrics: {:?}", e);
+            Ok(frontend)
+        }
+    }
+}
+
+/// Tower layer recording the count and latency of every gRPC call.
+#[derive(Clone)]
+pub struct GrpcMetricsLayer {
+    metrics: Arc<FrontendMetrics>,
+}
+
+impl GrpcMetricsLayer {
+    pub fn new(metrics: Arc<FrontendMetrics>) -> Self {
+        GrpcMetricsLayer { metrics }
+    }
+}
+
+impl<S> Layer<S> for GrpcMetricsLayer {
+    type Service = GrpcMetricsService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        GrpcMetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+        }
+    }
+}
+
+/// Path prefix of the calls of the inference service.
+const GRPC_SERVICE_PREFIX: &str = "/infer_proto.GRPCInferenceService/";
+
+/// Methods of the inference service, the only values of the method label.
+const GRPC_METHODS: [&str; 15] = [
+    "ServerLive",
+    "ServerReady",
+    "ModelReady",
+    "ServerMetadata",
+    "ModelMetadata",
+    "ModelInfer",
+    "ModelStatistics",
+    "ModelConfig",
+    "ModelStreamInfer",
+    "RepositoryIndex",
+    "RepositoryModelLoad",
+    "RepositoryModelUnload",
+    "SystemSharedMemoryStatus",
+    "SystemSharedMemoryRegister",
+    "SystemSharedMemoryUnregister",
+];
+
+/// Returns the method label of a call to `path`, "unknown" for anything but
+/// a method of the inference service so clients can't add label values.
+fn grpc_method(path: &str) -> &'static str {
+    path.strip_prefix(GRPC_SERVICE_PREFIX)
+        .and_then(|method| GRPC_METHODS.iter().find(|known| **known == method))
+        .copied()
+        .unwrap_or("unknown")
+}
+
+#[derive(Clone)]
+pub struct GrpcMetricsService<S> {
+    inner: S,
+    metrics: Arc<FrontendMetrics>,
+}
+
+impl<S, ReqBody, ResBody> Service<http::Request<ReqBody>> for GrpcMetricsService<S>
+where
+    S: Service<http::Request<ReqBody>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    ReqBody: Send + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: http::Request<ReqBody>) -> Self::Future {
+        let method = grpc_method(request.uri().path());
+        let metrics = self.metrics.clone();
+        let start = Instant::now();
+        // the service polled ready is the one that must handle the call
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        Box::pin(async move {
+            let response = inner.call(request).await?;
+            // errors are sent as trailers-only responses, successful calls
+            // carry their status in the trailers
+            let code = response
+                .headers()
+                .get("grpc-status")
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or("0")
+                .to_owned();
+            metrics.observe_grpc(method, &code, start.elapsed());
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frontend_metrics() {
+        let metrics = FrontendMetrics::new().unwrap();
+        metrics.observe_grpc("ModelInfer", "0", Duration::from_millis(2));
+        metrics.observe_conversion(Conversion::Request, Duration::from_micros(20));
+        let text = metrics.formatted().unwrap();
+        assert!(text.contains(r#"rustis_grpc_requests_total{code="0",method="ModelInfer"} 1"#));
+        assert!(text.contains(
+            r#"rustis_protobuf_conversion_duration_seconds_count{conversion="request"} 1"#
+        ));
+    }
+
+    #[test]
+    fn test_grpc_method() {
+        assert_eq!(
+            grpc_method("/infer_proto.GRPCInferenceService/ModelInfer"),
+            "ModelInfer"
+        );
+        assert_eq!(
+            grpc_method("/infer_proto.GRPCInferenceService/NoSuchMethod"),
+            "unknown"
+        );
+        assert_eq!(grpc_method("/other.Service/ModelInfer"), "unknown");
+        assert_eq!(grpc_method("/"), "unknown");
+    }
+
+    #[test]
+    fn test_exposition() {
+        let metrics = FrontendMetrics::new().unwrap();
+        metrics.observe_grpc("ModelInfer", "0", Duration::from_millis(2));
+        let text = exposition::<&str>(Ok("nv_inference_count 1\n".to_owned()), &metrics).unwrap();
+        assert!(text.starts_with("nv_inference_count 1\n"));
+        assert!(text.contains("rustis_grpc_requests_total"));
+        // the frontend metrics survive a failing server
+        let text = exposition(Err("server unavailable"), &metrics).unwrap();
+        assert!(text.contains("rustis_grpc_requests_total"));
+    }
+}
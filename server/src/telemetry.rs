@@ -0,0 +1,301 @@
+//! OpenTelemetry export of the spans of traced inference requests, to a
+//! collector over OTLP/HTTP with the JSON encoding, or to a file for local
+//! testing. Spans join the caller's trace given by its `traceparent` header.
+//!
+//! Only the subset of OTLP the spans need is implemented: JSON over HTTP/1.1,
+//! without compression, authentication headers nor retries. A failed export
+//! is logged and its spans dropped. Queued spans are sent on `flush`, which
+//! the server calls on shutdown.
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::thread::JoinHandle;
+
+use axum::http::HeaderMap;
+use hyper::{header, Body, Client, Method, Request, Uri};
+use log::error;
+use serde_json::{json, Value};
+use tokio::sync::{mpsc, oneshot};
+use tonic::metadata::MetadataMap;
+use triton_sys::wrapper::trace::{
+    to_hex, AttributeValue, Span, SpanEvent, SpanExporter, SpanKind, TraceContext,
+};
+
+const TRACEPARENT_HEADER: &str = "traceparent";
+const SCOPE_NAME: &str = "rustis";
+const MAX_BATCH_SIZE: usize = 512;
+
+enum Message {
+    Spans(Vec<Span>),
+    // acknowledged once the spans queued before it are sent
+    Flush(oneshot::Sender<()>),
+}
+
+fn flush(sender: &mpsc::UnboundedSender<Message>) {
+    let (done, flushed) = oneshot::channel();
+    if sender.send(Message::Flush(done)).is_ok() {
+        let _ = flushed.blocking_recv();
+    }
+}
+
+pub fn grpc_trace_context(metadata: &MetadataMap) -> Option<TraceContext> {
+    let header = metadata.get(TRACEPARENT_HEADER)?.to_str().ok()?;
+    TraceContext::from_traceparent(header)
+}
+
+pub fn http_trace_context(headers: &HeaderMap) -> Option<TraceContext> {
+    let header = headers.get(TRACEPARENT_HEADER)?.to_str().ok()?;
+    TraceContext::from_traceparent(header)
+}
+
+/// Posts spans to an OTLP/HTTP endpoint such as `http://localhost:4318/v1/traces`.
+/// Spans are queued and sent by a background task, so the exporter must be
+/// created within the tokio runtime.
+pub struct OtlpExporter {
+    sender: mpsc::UnboundedSender<Message>,
+}
+
+impl OtlpExporter {
+    pub fn new(endpoint: &str, service_name: &str) -> Result<Self, hyper::http::uri::InvalidUri> {
+        let uri = endpoint.parse::<Uri>()?;
+        let service_name = service_name.to_owned();
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            let client = Client::new();
+            while let Some(message) = receiver.recv().await {
+                let mut spans = match message {
+                    Message::Spans(spans) => spans,
+                    Message::Flush(done) => {
+                        let _ = done.send(());
+                        continue;
+                    }
+                };
+                // whatever is pending goes out in the same request
+                let mut flushed = None;
+                while spans.len() < MAX_BATCH_SIZE && flushed.is_none() {
+                    match receiver.try_recv() {
+                        Ok(Message::Spans(more)) => spans.extend(more),
+                        Ok(Message::Flush(done)) => flushed = Some(done),
+                        Err(_) => break,
+                    }
+                }
+                let request = Request::builder()
+                    .method(Method::POST)
+                    .uri(uri.clone())
+                    .header(header::CONTENT_TYPE, "application/json")
+                    .body(Body::from(
+                        export_request(&service_name, &spans).to_string(),
+                    ));
+                let request = match request {
+                    Ok(r) => r,
+                    Err(e) => {
+                        error!("error building OTLP export request: {}", e);
+                        continue;
+                    }
+                };
+                match client.request(request).await {
+                    Ok(r) if r.status().is_success() => {}
+                    Ok(r) => error!(
+                        "OTLP export of {} spans failed: {}",
+                        spans.len(),
+                        r.status()
+                    ),
+                    Err(e) => error!("OTLP export of {} spans failed: {}", spans.len(), e),
+                }
+                if let Some(done) = flushed {
+                    let _ = done.send(());
+                }
+            }
+        });
+        Ok(OtlpExporter { sender })
+    }
+}
+
+impl SpanExporter for OtlpExporter {
+    fn export(&self, spans: Vec<Span>) {
+        // the task only ends with the runtime
+        let _ = self.sender.send(Message::Spans(spans));
+    }
+
+    fn flush(&self) {
+        flush(&self.sender);
+    }
+}
+
+/// Appends one OTLP/JSON export request per line to a file, the format of
+/// the OpenTelemetry collector's file exporter. Spans are written by a
+/// dedicated thread, which ends once the exporter is dropped.
+pub struct FileExporter {
+    sender: Option<mpsc::UnboundedSender<Message>>,
+    writer: Option<JoinHandle<()>>,
+}
+
+impl FileExporter {
+    pub fn new(path: impl AsRef<Path>, service_name: &str) -> std::io::Result<Self> {
+        let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+        let service_name = service_name.to_owned();
+        let (sender, mut receiver) = mpsc::unbounded_channel::<Message>();
+        let writer = std::thread::Builder::new()
+            .name("trace-file-writer".to_owned())
+            .spawn(move || {
+                while let Some(message) = receiver.blocking_recv() {
+                    match message {
+                        Message::Spans(spans) => {
+                            let line = export_request(&service_name, &spans).to_string();
+                            if let Err(e) = writeln!(file, "{}", line) {
+                                error!("error writing spans to trace file: {}", e);
+                            }
+                        }
+                        Message::Flush(done) => {
+                            let _ = done.send(());
+                        }
+                    }
+                }
+            })?;
+        Ok(FileExporter {
+            sender: Some(sender),
+            writer: Some(writer),
+        })
+    }
+}
+
+impl SpanExporter for FileExporter {
+    fn export(&self, spans: Vec<Span>) {
+        if let Some(sender) = &self.sender {
+            // the writer only ends once the sender is dropped
+            let _ = sender.send(Message::Spans(spans));
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(sender) = &self.sender {
+            flush(sender);
+        }
+    }
+}
+
+impl Drop for FileExporter {
+    fn drop(&mut self) {
+        // closing the channel lets the writer finish the queued spans
+        self.sender.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+}
+
+/// `ExportTraceServiceRequest` in the OTLP/JSON encoding.
+fn export_request(service_name: &str, spans: &[Span]) -> Value {
+    json!({
+        "resourceSpans": [{
+            "resource": {
+                "attributes": [attribute(
+                    "service.name",
+                    &AttributeValue::String(service_name.to_owned()),
+                )],
+            },
+            "scopeSpans": [{
+                "scope": { "name": SCOPE_NAME },
+                "spans": spans.iter().map(span).collect::<Vec<_>>(),
+            }],
+        }],
+    })
+}
+
+fn span(span: &Span) -> Value {
+    json!({
+        "traceId": to_hex(&span.trace_id),
+        "spanId": to_hex(&span.span_id),
+        "parentSpanId": span.parent_span_id.map(|id| to_hex(&id)).unwrap_or_default(),
+        "name": span.name,
+        "kind": match span.kind {
+            SpanKind::Internal => 1,
+            SpanKind::Server => 2,
+        },
+        // 64 bit integers are strings in OTLP/JSON
+        "startTimeUnixNano": span.start_time_unix_nano.to_string(),
+        "endTimeUnixNano": span.end_time_unix_nano.to_string(),
+        "attributes": attributes(&span.attributes),
+        "events": span.events.iter().map(event).collect::<Vec<_>>(),
+    })
+}
+
+fn event(event: &SpanEvent) -> Value {
+    json!({
+        "timeUnixNano": event.time_unix_nano.to_string(),
+        "name": event.name,
+        "attributes": attributes(&event.attributes),
+    })
+}
+
+fn attributes(attributes: &[(String, AttributeValue)]) -> Vec<Value> {
+    attributes
+        .iter()
+        .map(|(key, value)| attribute(key, value))
+        .collect()
+}
+
+fn attribute(key: &str, value: &AttributeValue) -> Value {
+    let value = match value {
+        AttributeValue::String(v) => json!({ "stringValue": v }),
+        AttributeValue::Int(v) => json!({ "intValue": v.to_string() }),
+    };
+    json!({ "key": key, "value": value })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_export() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            TRACEPARENT_HEADER,
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+        let context = grpc_trace_context(&metadata).unwrap();
+        assert_eq!(grpc_trace_context(&MetadataMap::new()), None);
+
+        let span = Span {
+            trace_id: context.trace_id,
+            span_id: [1; 8],
+            parent_span_id: Some(context.span_id),
+            name: "add_sub".to_owned(),
+            kind: SpanKind::Server,
+            start_time_unix_nano: 1_000,
+            end_time_unix_nano: 2_000,
+            attributes: vec![("triton.model_version".to_owned(), AttributeValue::Int(1))],
+            events: vec![SpanEvent {
+                name: "REQUEST_START".to_owned(),
+                time_unix_nano: 1_000,
+                attributes: vec![],
+            }],
+        };
+        let path = std::env::temp_dir().join(format!("rustis-trace-{}.json", std::process::id()));
+        let exporter = FileExporter::new(&path, "rustis").unwrap();
+        exporter.export(vec![span.clone()]);
+        exporter.export(vec![span]);
+        exporter.flush();
+        let contents = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let lines: Vec<&str> = contents.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let request: Value = serde_json::from_str(lines[0]).unwrap();
+        let resource = &request["resourceSpans"][0];
+        assert_eq!(
+            resource["resource"]["attributes"][0]["value"]["stringValue"],
+            "rustis"
+        );
+        let span = &resource["scopeSpans"][0]["spans"][0];
+        assert_eq!(span["traceId"], "4bf92f3577b34da6a3ce929d0e0e4736");
+        assert_eq!(span["parentSpanId"], "00f067aa0ba902b7");
+        assert_eq!(span["kind"], 2);
+        assert_eq!(span["startTimeUnixNano"], "1000");
+        assert_eq!(span["attributes"][0]["value"]["intValue"], "1");
+        assert_eq!(span["events"][0]["name"], "REQUEST_START");
+    }
+}
@@ -0,0 +1,229 @@
+//! Inference timeouts derived from the client deadline, the `timeout` request
+//! parameter and the per-model defaults of the frontend.
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
+
+use infer_proto::infer_proto::infer_parameter::ParameterChoice;
+use infer_proto::infer_proto::ModelInferRequest;
+use tonic::metadata::MetadataMap;
+use triton_sys::wrapper::error::{Result, RustisError};
+
+/// Timeout of unary requests without a deadline, a `timeout` parameter or a
+/// model default.
+pub const DEFAULT_TIMEOUT_MICRO_SECONDS: u64 = 5_000_000;
+
+const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// Default inference timeouts of the served models.
+#[derive(Debug, Clone)]
+pub struct InferTimeouts {
+    fallback: Duration,
+    models: HashMap<String, Duration>,
+}
+
+impl Default for InferTimeouts {
+    fn default() -> Self {
+        InferTimeouts {
+            fallback: Duration::from_micros(DEFAULT_TIMEOUT_MICRO_SECONDS),
+            models: HashMap::new(),
+        }
+    }
+}
+
+impl InferTimeouts {
+    /// Parses per-model defaults written as `<model>=<microseconds>,...`.
+    pub fn parse(spec: &str) -> Result<Self> {
+        let mut timeouts = InferTimeouts::default();
+        for entry in spec.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+            let (model, micros) = entry.split_once('=').ok_or_else(|| {
+                RustisError::Validation(format!("invalid model timeout '{}'", entry))
+            })?;
+            let micros = micros.trim().parse::<u64>().map_err(|e| {
+                RustisError::Validation(format!("invalid model timeout '{}': {}", entry, e))
+            })?;
+            timeouts.set_model_timeout(model.trim(), Duration::from_micros(micros));
+        }
+        Ok(timeouts)
+    }
+
+    pub fn set_model_timeout(&mut self, model: &str, timeout: Duration) {
+        self.models.insert(model.to_owned(), timeout);
+    }
+
+    /// Returns the tightest of the time left until `deadline`, the `timeout`
+    /// parameter of `request` and the default of the requested model. The
+    /// fallback only applies when none of them is set.
+    pub fn request_timeout(
+        &self,
+        request: &ModelInferRequest,
+        deadline: Option<Instant>,
+    ) -> Result<Duration> {
+        Ok(self
+            .requested_timeout(request, deadline)?
+            .unwrap_or(self.fallback))
+    }
+
+    /// Like `request_timeout`, but without the fallback. Streams use it, the
+    /// responses of decoupled models may keep coming for longer than any
+    /// fixed cap.
+    pub fn requested_timeout(
+        &self,
+        request: &ModelInferRequest,
+        deadline: Option<Instant>,
+    ) -> Result<Option<Duration>> {
+        let limits = [
+            deadline.map(|d| d.saturating_duration_since(Instant::now())),
+            parameter_timeout(request)?,
+            self.models.get(&request.model_name).copied(),
+        ];
+        Ok(limits.into_iter().flatten().min())
+    }
+}
+
+/// Reads the `timeout` parameter in microseconds, zero means no timeout as in Triton.
+fn parameter_timeout(request: &ModelInferRequest) -> Result<Option<Duration>> {
+    let choice = request
+        .parameters
+        .get("timeout")
+        .and_then(|p| p.parameter_choice.as_ref());
+    match choice {
+        None | Some(ParameterChoice::Int64Param(0)) => Ok(None),
+        Some(ParameterChoice::Int64Param(v)) if *v > 0 => {
+            Ok(Some(Duration::from_micros(*v as u64)))
+        }
+        Some(_) => Err(RustisError::Validation(
+            "parameter 'timeout' must be a non-negative int64 of microseconds".to_string(),
+        )),
+    }
+}
+
+/// Returns the deadline set by the client through the `grpc-timeout` header.
+pub fn grpc_deadline(metadata: &MetadataMap) -> Result<Option<Instant>> {
+    let value = match metadata.get(GRPC_TIMEOUT_HEADER) {
+        Some(v) => v,
+        None => return Ok(None),
+    };
+    let invalid = || {
+        RustisError::Validation(format!(
+            "invalid {} header: {:?}",
+            GRPC_TIMEOUT_HEADER, value
+        ))
+    };
+    // at most 8 digits followed by a unit
+    let value = value.to_str().map_err(|_| invalid())?;
+    if value.len() < 2 || value.len() > 9 {
+        return Err(invalid());
+    }
+    let (amount, unit) = value.split_at(value.len() - 1);
+    let amount = amount.parse::<u64>().map_err(|_| invalid())?;
+    let timeout = match unit {
+        "H" => Duration::from_secs(amount * 3600),
+        "M" => Duration::from_secs(amount * 60),
+        "S" => Duration::from_secs(amount),
+        "m" => Duration::from_millis(amount),
+        "u" => Duration::from_micros(amount),
+        "n" => Duration::from_nanos(amount),
+        _ => return Err(invalid()),
+    };
+    Ok(Some(Instant::now() + timeout))
+}
+
+/// Waits at most `timeout` for `infer`, failing with the error Triton
+/// reports for expired requests. Triton only enforces the timeout while
+/// requests are queued, and reads zero as no timeout.
+pub async fn with_timeout<T, F>(timeout: Duration, infer: F) -> Result<T>
+where
+    F: Future<Output = Result<T>>,
+{
+    if timeout.is_zero() {
+        return Err(RustisError::Timeout);
+    }
+    match tokio::time::timeout(timeout, infer).await {
+        Ok(r) => r,
+        Err(_) => Err(RustisError::Timeout),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use infer_proto::infer_proto::InferParameter;
+
+    fn request(model_name: &str, timeout: Option<i64>) -> ModelInferRequest {
+        let mut request = ModelInferRequest {
+            model_name: model_name.to_owned(),
+            ..Default::default()
+        };
+        if let Some(timeout) = timeout {
+            request.parameters.insert(
+                "timeout".to_owned(),
+                InferParameter {
+                    parameter_choice: Some(ParameterChoice::Int64Param(timeout)),
+                },
+            );
+        }
+        request
+    }
+
+    #[test]
+    fn test_request_timeout() {
+        let timeouts = InferTimeouts::parse("batch=600000000, fast=1000").unwrap();
+        assert!(InferTimeouts::parse("batch").is_err());
+
+        let fallback = Duration::from_micros(DEFAULT_TIMEOUT_MICRO_SECONDS);
+        let timeout = |request, deadline| timeouts.request_timeout(&request, deadline).unwrap();
+        assert_eq!(timeout(request("simple", None), None), fallback);
+        assert_eq!(timeout(request("simple", Some(0)), None), fallback);
+        assert_eq!(
+            timeout(request("batch", None), None),
+            Duration::from_secs(600)
+        );
+        assert_eq!(
+            timeout(request("batch", Some(20_000)), None),
+            Duration::from_millis(20)
+        );
+        assert_eq!(
+            timeout(request("fast", Some(20_000)), None),
+            Duration::from_millis(1)
+        );
+        let deadline = Instant::now() + Duration::from_secs(30);
+        assert!(timeout(request("simple", None), Some(deadline)) <= Duration::from_secs(30));
+        assert!(timeouts
+            .request_timeout(&request("simple", Some(-1)), None)
+            .is_err());
+
+        // streams without a deadline are not capped by the fallback
+        let requested = |request, deadline| timeouts.requested_timeout(&request, deadline).unwrap();
+        assert_eq!(requested(request("simple", None), None), None);
+        assert_eq!(
+            requested(request("simple", Some(20_000)), None),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(
+            requested(request("batch", None), None),
+            Some(Duration::from_secs(600))
+        );
+
+        let mut metadata = MetadataMap::new();
+        assert!(grpc_deadline(&metadata).unwrap().is_none());
+        metadata.insert(GRPC_TIMEOUT_HEADER, "250m".parse().unwrap());
+        let deadline = grpc_deadline(&metadata).unwrap().unwrap();
+        assert!(deadline <= Instant::now() + Duration::from_millis(250));
+        metadata.insert(GRPC_TIMEOUT_HEADER, "250x".parse().unwrap());
+        assert!(grpc_deadline(&metadata).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_with_timeout() {
+        let reply = with_timeout(Duration::from_millis(10), async {
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            Ok(())
+        })
+        .await;
+        assert_eq!(reply.unwrap_err(), RustisError::Timeout);
+        assert!(with_timeout(Duration::ZERO, async { Ok(()) })
+            .await
+            .is_err());
+    }
+}
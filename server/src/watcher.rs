@@ -0,0 +1,426 @@
+//! Watches the model repositories with notify and applies their changes
+//! once they settle: through PollModelRepository in the poll model control
+//! mode, or by loading and unloading the changed models in the explicit mode.
+//! Triton itself never polls an in-process server.
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Component, Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::{error, info, warn};
+use notify_debouncer_full::notify::{self, RecursiveMode, Watcher};
+use notify_debouncer_full::{new_debouncer, DebounceEventResult, DebouncedEvent};
+use tokio::sync::mpsc;
+use triton_sys::wrapper::error::RustisError;
+use triton_sys::wrapper::model_index::{ModelIndexEntry, ModelState};
+use triton_sys::wrapper::server::Server;
+use triton_sys::wrapper::server_options::ModelControlMode;
+
+/// How repository changes are applied.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WatchMode {
+    /// Poll the repositories, Triton works out what changed.
+    Poll,
+    /// Load, or unload, every model whose directory changed.
+    Explicit,
+}
+
+impl WatchMode {
+    /// The model control mode the server must run in.
+    pub fn control_mode(&self) -> ModelControlMode {
+        match self {
+            WatchMode::Poll => ModelControlMode::Poll,
+            WatchMode::Explicit => ModelControlMode::Explicit,
+        }
+    }
+}
+
+impl std::str::FromStr for WatchMode {
+    type Err = RustisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "poll" => Ok(WatchMode::Poll),
+            "explicit" => Ok(WatchMode::Explicit),
+            _ => Err(RustisError::Validation(format!(
+                "repository watch mode must be 'poll' or 'explicit', got '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+pub struct RepositoryWatcher {
+    server: Arc<Server>,
+    repositories: Vec<PathBuf>,
+    mode: WatchMode,
+    debounce: Duration,
+}
+
+impl RepositoryWatcher {
+    /// Changes to a path are applied once it saw no event for `debounce`, so
+    /// that copying a model is not picked up halfway.
+    pub fn new(
+        server: Arc<Server>,
+        repositories: Vec<PathBuf>,
+        mode: WatchMode,
+        debounce: Duration,
+    ) -> Self {
+        RepositoryWatcher {
+            server,
+            repositories,
+            mode,
+            debounce,
+        }
+    }
+
+    /// Starts watching. Events are debounced on notify's threads and applied
+    /// by a task of the current runtime.
+    pub fn spawn(self) -> notify::Result<()> {
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let mut debouncer = new_debouncer(self.debounce, None, move |result| {
+            // the task only ends with the runtime
+            let _ = sender.send(result);
+        })?;
+        for repository in &self.repositories {
+            debouncer
+                .watcher()
+                .watch(repository, RecursiveMode::Recursive)?;
+            // file ids tell renames apart from deletions
+            debouncer
+                .cache()
+                .add_root(repository, RecursiveMode::Recursive);
+        }
+        tokio::spawn(async move {
+            // watching stops when the debouncer is dropped
+            let _debouncer = debouncer;
+            self.apply_changes(receiver).await
+        });
+        Ok(())
+    }
+
+    async fn apply_changes(self, mut receiver: mpsc::UnboundedReceiver<DebounceEventResult>) {
+        while let Some(result) = receiver.recv().await {
+            let events = match result {
+                Ok(events) => events,
+                Err(errors) => {
+                    for e in errors {
+                        error!("error watching model repositories: {}", e);
+                    }
+                    continue;
+                }
+            };
+            let changes = changes(&self.repositories, &events);
+            if changes == Changes::Models(BTreeSet::new()) {
+                continue;
+            }
+            let server = self.server.clone();
+            let repositories = self.repositories.clone();
+            let mode = self.mode;
+            // loading blocks until the models are ready
+            let applied =
+                tokio::task::spawn_blocking(move || apply(&server, mode, &repositories, changes));
+            if let Err(e) = applied.await {
+                error!("error applying repository changes: {}", e);
+            }
+        }
+    }
+}
+
+/// What changed in the repositories.
+#[derive(Debug, PartialEq, Eq)]
+enum Changes {
+    /// The model directories with changes, by repository.
+    Models(BTreeSet<(PathBuf, String)>),
+    /// Events were dropped, so any model may have changed.
+    Rescan,
+}
+
+fn changes(repositories: &[PathBuf], events: &[DebouncedEvent]) -> Changes {
+    if events.iter().any(|e| e.need_rescan()) {
+        return Changes::Rescan;
+    }
+    let models = events
+        .iter()
+        .flat_map(|e| e.paths.iter())
+        .filter_map(|path| changed_model(repositories, path))
+        .collect();
+    Changes::Models(models)
+}
+
+fn apply(server: &Server, mode: WatchMode, repositories: &[PathBuf], changes: Changes) {
+    let before = model_index(server);
+    let changes = match changes {
+        Changes::Models(models) => models,
+        Changes::Rescan => {
+            warn!("model repository events were dropped, rescanning the repositories");
+            every_model(repositories, &before)
+        }
+    };
+    match mode {
+        WatchMode::Poll => {
+            if let Err(e) = server.poll_model_repository() {
+                error!("error polling model repositories: {}", e.msg());
+            }
+        }
+        WatchMode::Explicit => {
+            for (repository, model) in &changes {
+                if repository.join(model).is_dir() {
+                    if let Err(e) = server.load_model(model) {
+                        error!("error loading model '{}': {}", model, e.msg());
+                    }
+                } else if let Err(e) = server.unload_model(model) {
+                    error!("error unloading model '{}': {}", model, e.msg());
+                }
+            }
+        }
+    }
+    let after = model_index(server);
+    let models = changes
+        .iter()
+        .map(|(repository, model)| (model.clone(), repository.join(model).is_dir()))
+        .collect();
+    for outcome in outcomes(&models, &before, &after) {
+        match outcome {
+            Outcome::Failed { model, reason } => {
+                error!("model '{}' failed to load: {}", model, reason)
+            }
+            outcome => info!("{}", outcome),
+        }
+    }
+}
+
+// the model directories of the repositories, and the models of the index
+// that no longer have one
+fn every_model(repositories: &[PathBuf], index: &[ModelIndexEntry]) -> BTreeSet<(PathBuf, String)> {
+    let mut models = BTreeSet::new();
+    for repository in repositories {
+        let entries = match std::fs::read_dir(repository) {
+            Ok(entries) => entries,
+            Err(e) => {
+                error!("error reading {}: {}", repository.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            if entry.file_type().map(|t| t.is_dir()).unwrap_or(false) {
+                models.extend(changed_model(repositories, &entry.path()));
+            }
+        }
+    }
+    if let Some(repository) = repositories.first() {
+        for entry in index {
+            if !models.iter().any(|(_, model)| *model == entry.name) {
+                models.insert((repository.clone(), entry.name.clone()));
+            }
+        }
+    }
+    models
+}
+
+fn model_index(server: &Server) -> Vec<ModelIndexEntry> {
+    match server.model_index(false) {
+        Ok(index) => index,
+        Err(e) => {
+            error!("error reading the model index: {}", e);
+            vec![]
+        }
+    }
+}
+
+/// What a repository change did to a model.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Outcome {
+    Loaded { model: String, version: String },
+    Reloaded { model: String, version: String },
+    Unloaded { model: String, version: String },
+    Failed { model: String, reason: String },
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Loaded { model, version } => {
+                write!(f, "model '{}' version {} loaded", model, version)
+            }
+            Outcome::Reloaded { model, version } => {
+                write!(f, "model '{}' version {} reloaded", model, version)
+            }
+            Outcome::Unloaded { model, version } => {
+                write!(f, "model '{}' version {} unloaded", model, version)
+            }
+            Outcome::Failed { model, reason } => {
+                write!(f, "model '{}' failed to load: {}", model, reason)
+            }
+        }
+    }
+}
+
+// compares the index entries of the changed models before and after applying,
+// `models` tells whether their directory still exists
+fn outcomes(
+    models: &BTreeMap<String, bool>,
+    before: &[ModelIndexEntry],
+    after: &[ModelIndexEntry],
+) -> Vec<Outcome> {
+    let ready = |index: &[ModelIndexEntry], model: &str| -> BTreeSet<String> {
+        index
+            .iter()
+            .filter(|e| e.name == model && e.state == ModelState::Ready)
+            .map(|e| e.version.clone())
+            .collect()
+    };
+    let mut outcomes = vec![];
+    for (model, present) in models {
+        let (was_ready, is_ready) = (ready(before, model), ready(after, model));
+        // versions of removed models are unavailable because they were unloaded
+        let unloaded = |version: &String| {
+            !present && was_ready.contains(version) && !is_ready.contains(version)
+        };
+        let failure = after.iter().find(|e| {
+            e.name == *model
+                && e.state != ModelState::Ready
+                && !e.reason.is_empty()
+                && !unloaded(&e.version)
+        });
+        if let Some(failure) = failure {
+            outcomes.push(Outcome::Failed {
+                model: model.clone(),
+                reason: failure.reason.clone(),
+            });
+        }
+        for version in &is_ready {
+            let (model, version) = (model.clone(), version.clone());
+            outcomes.push(match was_ready.contains(&version) {
+                true => Outcome::Reloaded { model, version },
+                false => Outcome::Loaded { model, version },
+            });
+        }
+        for version in was_ready.difference(&is_ready) {
+            outcomes.push(Outcome::Unloaded {
+                model: model.clone(),
+                version: version.clone(),
+            });
+        }
+    }
+    outcomes
+}
+
+// the repository and model directory `path` belongs to, hidden entries are
+// not models
+fn changed_model(repositories: &[PathBuf], path: &Path) -> Option<(PathBuf, String)> {
+    repositories.iter().find_map(|repository| {
+        let model = match path.strip_prefix(repository).ok()?.components().next()? {
+            Component::Normal(model) => model.to_str()?,
+            _ => return None,
+        };
+        if model.starts_with('.') {
+            return None;
+        }
+        Some((repository.clone(), model.to_owned()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, version: &str, state: ModelState, reason: &str) -> ModelIndexEntry {
+        ModelIndexEntry {
+            name: name.to_owned(),
+            version: version.to_owned(),
+            state,
+            reason: reason.to_owned(),
+        }
+    }
+
+    #[test]
+    fn test_outcomes() {
+        let before = vec![
+            entry("addsub", "1", ModelState::Ready, ""),
+            entry("removed", "1", ModelState::Ready, ""),
+        ];
+        let after = vec![
+            entry("addsub", "1", ModelState::Ready, ""),
+            entry("addsub", "2", ModelState::Ready, ""),
+            entry("removed", "1", ModelState::Unavailable, "unloaded"),
+            entry("broken", "1", ModelState::Unavailable, "invalid config"),
+        ];
+        let models = [
+            ("addsub", true),
+            ("broken", true),
+            ("removed", false),
+            ("untouched", true),
+        ]
+        .iter()
+        .map(|(m, present)| (m.to_string(), *present))
+        .collect();
+        assert_eq!(
+            outcomes(&models, &before, &after),
+            vec![
+                Outcome::Reloaded {
+                    model: "addsub".to_owned(),
+                    version: "1".to_owned(),
+                },
+                Outcome::Loaded {
+                    model: "addsub".to_owned(),
+                    version: "2".to_owned(),
+                },
+                Outcome::Failed {
+                    model: "broken".to_owned(),
+                    reason: "invalid config".to_owned(),
+                },
+                Outcome::Unloaded {
+                    model: "removed".to_owned(),
+                    version: "1".to_owned(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_watch_repository() {
+        let repository = std::env::temp_dir().join(format!("rustis-watch-{}", std::process::id()));
+        std::fs::create_dir_all(repository.join("addsub").join("1")).unwrap();
+        let repositories = vec![repository.clone()];
+        let (sender, receiver) = std::sync::mpsc::channel();
+        let mut debouncer = new_debouncer(Duration::from_millis(100), None, sender).unwrap();
+        debouncer
+            .watcher()
+            .watch(&repository, RecursiveMode::Recursive)
+            .unwrap();
+
+        std::fs::create_dir(repository.join("addsub").join("2")).unwrap();
+        std::fs::write(repository.join("addsub").join("2").join("model.py"), "").unwrap();
+        let events = receiver
+            .recv_timeout(Duration::from_secs(5))
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            changes(&repositories, &events),
+            Changes::Models(BTreeSet::from([(repository.clone(), "addsub".to_owned())]))
+        );
+        assert_eq!(changed_model(&repositories, &repository.join(".tmp")), None);
+        assert_eq!(changed_model(&repositories, Path::new("/elsewhere")), None);
+
+        // dropped events, such as an inotify queue overflow, rescan everything
+        let overflow =
+            notify::Event::new(notify::EventKind::Other).set_flag(notify::event::Flag::Rescan);
+        let events = [DebouncedEvent::new(overflow, std::time::Instant::now())];
+        assert_eq!(changes(&repositories, &events), Changes::Rescan);
+        let index = [ModelIndexEntry {
+            name: "removed".to_owned(),
+            ..Default::default()
+        }];
+        assert_eq!(
+            every_model(&repositories, &index),
+            BTreeSet::from([
+                (repository.clone(), "addsub".to_owned()),
+                (repository.clone(), "removed".to_owned()),
+            ])
+        );
+
+        drop(debouncer);
+        std::fs::remove_dir_all(&repository).unwrap();
+    }
+}
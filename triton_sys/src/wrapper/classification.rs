@@ -0,0 +1,149 @@
+use crate::sys::*;
+
+use super::error::{Result, RustisError};
+use super::inference_response::{InferResponse, InferResponseOutput};
+
+/// Result of the classification extension for one output.
+#[derive(Debug, PartialEq)]
+pub struct Classification {
+    /// `[k]`, or `[batch_size, k]` when the output has a batch dimension.
+    pub shape: Vec<i64>,
+    /// `"value:index"` or `"value:index:label"` strings, best class first.
+    pub classes: Vec<Vec<u8>>,
+}
+
+impl Classification {
+    /// Classifies output `index` of `response`, keeping the `count` highest
+    /// scores of every batch entry. Outputs of rank two or more are read as
+    /// `[batch_size, classes...]`.
+    pub fn top_k(
+        response: &InferResponse,
+        index: u32,
+        output: &InferResponseOutput,
+        count: usize,
+    ) -> Result<Self> {
+        let data = match output.byte_size() {
+            0 => &[],
+            byte_size => unsafe {
+                std::slice::from_raw_parts(output.base() as *const u8, byte_size)
+            },
+        };
+        let scores = scores(output.datatype(), data).ok_or_else(|| {
+            RustisError::Unsupported(format!(
+                "class result not available for output '{}' due to unsupported type",
+                output.name()
+            ))
+        })?;
+        let batch_size = match output.shape().len() {
+            0 | 1 => 1,
+            _ => output.shape()[0].max(1) as usize,
+        };
+        let class_count = scores.len() / batch_size;
+        let k = count.min(class_count);
+
+        let mut classes = Vec::with_capacity(batch_size * k);
+        for batch in scores.chunks(class_count.max(1)).take(batch_size) {
+            for class_index in ranked(batch, k) {
+                let (_, value) = &batch[class_index];
+                let class = match response
+                    .output_classification_label(index, class_index)
+                    .map_err(RustisError::from)?
+                {
+                    Some(label) => format!("{}:{}:{}", value, class_index, label),
+                    None => format!("{}:{}", value, class_index),
+                };
+                classes.push(class.into_bytes());
+            }
+        }
+        let shape = match output.shape().len() {
+            0 | 1 => vec![k as i64],
+            _ => vec![batch_size as i64, k as i64],
+        };
+        Ok(Classification { shape, classes })
+    }
+}
+
+/// Reads every element of a numeric tensor as its score and its formatted
+/// value, or returns `None` for types that cannot be classified.
+#[allow(non_upper_case_globals)]
+fn scores(datatype: TRITONSERVER_DataType, data: &[u8]) -> Option<Vec<(f64, String)>> {
+    macro_rules! read {
+        ($t:ty) => {
+            read!($t, |v: $t| v.to_string())
+        };
+        ($t:ty, $format:expr) => {
+            data.chunks_exact(std::mem::size_of::<$t>())
+                .map(|b| {
+                    let v = <$t>::from_le_bytes(b.try_into().unwrap());
+                    (v as f64, $format(v))
+                })
+                .collect()
+        };
+    }
+    let scores = match datatype {
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT8 => read!(u8),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT16 => read!(u16),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT32 => read!(u32),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT64 => read!(u64),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT8 => read!(i8),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT16 => read!(i16),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32 => read!(i32),
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT64 => read!(i64),
+        // formatted like std::to_string, as Triton does
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32 => {
+            read!(f32, |v: f32| format!("{:.6}", v))
+        }
+        TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP64 => {
+            read!(f64, |v: f64| format!("{:.6}", v))
+        }
+        _ => return None,
+    };
+    Some(scores)
+}
+
+/// Returns the indices of the `k` highest scores, ties keep the lower index first.
+fn ranked(scores: &[(f64, String)], k: usize) -> Vec<usize> {
+    let mut indices: Vec<usize> = (0..scores.len()).collect();
+    indices.sort_by(|&a, &b| scores[b].0.total_cmp(&scores[a].0));
+    indices.truncate(k);
+    indices
+}
+
+/// Serializes BYTES elements for `raw_output_contents`, each prefixed by
+/// its 4-byte little-endian length.
+pub fn serialize_bytes<T: AsRef<[u8]>>(elements: &[T]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(elements.iter().map(|e| e.as_ref().len() + 4).sum());
+    for element in elements {
+        let element = element.as_ref();
+        data.extend_from_slice(&(element.len() as u32).to_le_bytes());
+        data.extend_from_slice(element);
+    }
+    data
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_ranked_scores() {
+        let data: Vec<u8> = [0.1f32, 0.7, 0.2, 0.7]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let fp32 = scores(TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32, &data).unwrap();
+        assert_eq!(fp32[1].1, "0.700000");
+        assert_eq!(ranked(&fp32, 3), vec![1, 3, 2]);
+
+        let data: Vec<u8> = [-3i32, 5].iter().flat_map(|v| v.to_le_bytes()).collect();
+        let int32 = scores(TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32, &data).unwrap();
+        assert_eq!(int32[0].1, "-3");
+        assert_eq!(ranked(&int32, 5), vec![1, 0]);
+
+        assert!(scores(TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BYTES, &[]).is_none());
+        assert_eq!(
+            serialize_bytes(&[b"0.7:1".to_vec()]),
+            b"\x05\x00\x00\x000.7:1".to_vec()
+        );
+    }
+}
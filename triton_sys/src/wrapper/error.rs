@@ -1,6 +1,24 @@
 use crate::sys::*;
 use core::fmt;
+use std::collections::HashMap;
 use std::error::Error;
+use std::ffi::CString;
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+/// Domain of the `google.rpc.ErrorInfo` detail attached to gRPC statuses.
+const ERROR_DOMAIN: &str = "triton.inference.server";
+
+/// Converts `value` for the C API, failing with an invalid argument error
+/// that names `what` when it contains NUL bytes.
+pub(crate) fn c_string(what: &str, value: &str) -> Result<CString, TritonError> {
+    CString::new(value).map_err(|_| {
+        TritonError::new(
+            TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG,
+            format!("{} must not contain NUL bytes", what),
+        )
+    })
+}
 
 #[derive(Debug)]
 pub struct TritonError {
@@ -12,7 +30,9 @@ impl TritonError {
         TritonError { _err: err }
     }
     pub fn new(code: TRITONSERVER_Error_Code, msg: String) -> Self {
-        let err_pointer = unsafe { TRITONSERVER_ErrorNew(code, msg.as_ptr() as *const i8) };
+        // Triton copies the message, which must be NUL-terminated
+        let c_msg = CString::new(msg.replace('\0', "\\0")).unwrap();
+        let err_pointer = unsafe { TRITONSERVER_ErrorNew(code, c_msg.as_ptr()) };
         TritonError { _err: err_pointer }
     }
 
@@ -54,19 +74,237 @@ impl Drop for TritonError {
 unsafe impl Send for TritonError {}
 unsafe impl Sync for TritonError {}
 
-#[derive(Debug)]
-pub struct InferError {
-    msg: String,
+/// Errors of Triton and of the wrapper as owned data, which can be matched
+/// on and moved across threads. Every kind maps to a Triton error code, so
+/// frontends report them the same way as errors from Triton.
+#[derive(Debug, Clone, PartialEq)]
+pub enum RustisError {
+    /// Reported by Triton, or raised by the wrapper with a given Triton code.
+    Triton {
+        code: TRITONSERVER_Error_Code,
+        msg: String,
+    },
+    /// A request, option or tensor rejected before it reached Triton.
+    Validation(String),
+    /// A model, region or other named resource that does not exist.
+    NotFound(String),
+    /// A feature that the wrapper or the model does not support.
+    Unsupported(String),
+    /// Data that could not be converted between Triton, protobuf and JSON.
+    Conversion(String),
+    /// The response channel of a request closed before the final response.
+    ChannelClosed,
+    /// The request did not complete before its timeout.
+    Timeout,
+    /// Any other failure of the wrapper.
+    Internal(String),
 }
-impl InferError {
-    pub fn new(msg: String) -> Self {
-        InferError { msg }
+
+pub type Result<T, E = RustisError> = std::result::Result<T, E>;
+
+impl RustisError {
+    #[allow(non_upper_case_globals)]
+    pub fn code(&self) -> TRITONSERVER_Error_Code {
+        match self {
+            RustisError::Triton { code, .. } => *code,
+            RustisError::Validation(_) => {
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG
+            }
+            RustisError::NotFound(_) => TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_NOT_FOUND,
+            RustisError::Unsupported(_) => {
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNSUPPORTED
+            }
+            // Triton reports expired request timeouts as unavailable
+            RustisError::Timeout => TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE,
+            RustisError::Conversion(_) | RustisError::ChannelClosed | RustisError::Internal(_) => {
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INTERNAL
+            }
+        }
+    }
+
+    pub fn msg(&self) -> String {
+        self.to_string()
+    }
+
+    /// Name of the variant, carried in gRPC statuses so that errors sharing
+    /// a status code can be told apart.
+    fn kind(&self) -> &'static str {
+        match self {
+            RustisError::Triton { .. } => "triton",
+            RustisError::Validation(_) => "validation",
+            RustisError::NotFound(_) => "not_found",
+            RustisError::Unsupported(_) => "unsupported",
+            RustisError::Conversion(_) => "conversion",
+            RustisError::ChannelClosed => "channel_closed",
+            RustisError::Timeout => "timeout",
+            RustisError::Internal(_) => "internal",
+        }
+    }
+}
+
+impl From<TritonError> for RustisError {
+    fn from(e: TritonError) -> Self {
+        RustisError::Triton {
+            code: e.code(),
+            msg: e.msg(),
+        }
     }
 }
-impl fmt::Display for InferError {
+
+impl From<serde_json::Error> for RustisError {
+    fn from(e: serde_json::Error) -> Self {
+        RustisError::Conversion(e.to_string())
+    }
+}
+
+impl fmt::Display for RustisError {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}", self.msg)
+        match self {
+            RustisError::Triton { msg, .. }
+            | RustisError::Validation(msg)
+            | RustisError::NotFound(msg)
+            | RustisError::Unsupported(msg)
+            | RustisError::Conversion(msg)
+            | RustisError::Internal(msg) => write!(f, "{}", msg),
+            RustisError::ChannelClosed => write!(f, "response channel closed"),
+            RustisError::Timeout => write!(f, "Request timeout expired"),
+        }
     }
 }
 
-impl Error for InferError {}
+impl Error for RustisError {}
+
+#[allow(non_upper_case_globals)]
+fn error_reason(code: TRITONSERVER_Error_Code) -> &'static str {
+    match code {
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INTERNAL => "INTERNAL",
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_NOT_FOUND => "NOT_FOUND",
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG => "INVALID_ARG",
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE => "UNAVAILABLE",
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNSUPPORTED => "UNSUPPORTED",
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_ALREADY_EXISTS => "ALREADY_EXISTS",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Maps the error to the gRPC status matching its Triton error code, which
+/// is attached as the reason of a `google.rpc.ErrorInfo` detail. The `kind`
+/// metadata of the detail names the variant.
+impl From<RustisError> for Status {
+    #[allow(non_upper_case_globals)]
+    fn from(e: RustisError) -> Self {
+        let code = e.code();
+        let grpc_code = match (&e, code) {
+            (RustisError::Timeout, _) => Code::DeadlineExceeded,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INTERNAL) => Code::Internal,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_NOT_FOUND) => Code::NotFound,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG) => {
+                Code::InvalidArgument
+            }
+            // Triton has no dedicated code for expired request timeouts
+            (
+                RustisError::Triton { msg, .. },
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE,
+            ) if msg.contains("timeout expired") => Code::DeadlineExceeded,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE) => Code::Unavailable,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNSUPPORTED) => Code::Unimplemented,
+            (_, TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_ALREADY_EXISTS) => {
+                Code::AlreadyExists
+            }
+            _ => Code::Unknown,
+        };
+        let metadata = HashMap::from([("kind".to_string(), e.kind().to_string())]);
+        let details = ErrorDetails::with_error_info(error_reason(code), ERROR_DOMAIN, metadata);
+        Status::with_error_details(grpc_code, e.msg(), details)
+    }
+}
+
+impl From<TritonError> for Status {
+    fn from(e: TritonError) -> Self {
+        RustisError::from(e).into()
+    }
+}
+
+/// Reads the status of a gRPC call back as the error it was created from.
+/// The variants sharing the internal status code are told apart by the
+/// `kind` metadata, Triton errors come back with the code of their status.
+impl From<Status> for RustisError {
+    fn from(status: Status) -> Self {
+        let msg = status.message().to_owned();
+        let kind = status
+            .get_details_error_info()
+            .and_then(|info| info.metadata.get("kind").cloned());
+        match kind.as_deref() {
+            Some("conversion") => return RustisError::Conversion(msg),
+            Some("channel_closed") => return RustisError::ChannelClosed,
+            _ => {}
+        }
+        match status.code() {
+            Code::InvalidArgument => RustisError::Validation(msg),
+            Code::NotFound => RustisError::NotFound(msg),
+            Code::Unimplemented => RustisError::Unsupported(msg),
+            Code::DeadlineExceeded => RustisError::Timeout,
+            Code::Internal => RustisError::Internal(msg),
+            Code::Unavailable => RustisError::Triton {
+                code: TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE,
+                msg,
+            },
+            Code::AlreadyExists => RustisError::Triton {
+                code: TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_ALREADY_EXISTS,
+                msg,
+            },
+            _ => RustisError::Triton {
+                code: TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNKNOWN,
+                msg,
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status() {
+        let status = Status::from(RustisError::Validation("Invalid shape".to_string()));
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Invalid shape");
+        let info = status.get_details_error_info().unwrap();
+        assert_eq!(info.reason, "INVALID_ARG");
+        assert_eq!(info.domain, ERROR_DOMAIN);
+        assert_eq!(
+            RustisError::from(status),
+            RustisError::Validation("Invalid shape".to_string())
+        );
+
+        let status = Status::from(RustisError::Timeout);
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+        assert_eq!(RustisError::from(status), RustisError::Timeout);
+        let status = Status::from(RustisError::Triton {
+            code: TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_UNAVAILABLE,
+            msg: "Request timeout expired".to_string(),
+        });
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+
+        let status = Status::from(RustisError::ChannelClosed);
+        assert_eq!(status.code(), Code::Internal);
+        assert_eq!(status.message(), "response channel closed");
+        assert_eq!(RustisError::from(status), RustisError::ChannelClosed);
+
+        let status = Status::from(RustisError::Conversion("invalid JSON".to_string()));
+        assert_eq!(status.code(), Code::Internal);
+        let info = status.get_details_error_info().unwrap();
+        assert_eq!(info.metadata["kind"], "conversion");
+        assert_eq!(
+            RustisError::from(status),
+            RustisError::Conversion("invalid JSON".to_string())
+        );
+        // statuses of other servers have no kind
+        let status = Status::internal("out of memory");
+        assert_eq!(
+            RustisError::from(status),
+            RustisError::Internal("out of memory".to_string())
+        );
+    }
+}
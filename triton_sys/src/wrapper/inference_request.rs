@@ -1,32 +1,111 @@
 use super::{
-    error::TritonError, inference_response::InferResponse, response_allocator::ResponseAllocator,
-    server::Server, utils,
+    error::{c_string, TritonError},
+    inference_response::InferResponse,
+    response_allocator::{OutputBuffers, ResponseAllocator},
+    server::Server,
+    shared_memory::SharedMemoryRegion,
+    trace::InferenceTrace,
+    utils,
 };
 use crate::sys::*;
 use log::error;
-use std::{ffi::{c_char, CString}};
+use std::collections::HashMap;
+use std::ffi::c_char;
+use std::sync::Arc;
 
-use futures::executor::block_on;
 use infer_proto::infer_proto;
 use std::{ffi::c_void, ptr};
-use tokio::{sync::mpsc::Receiver, sync::mpsc::Sender};
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
 
+/// Keeps the input data of a request alive until Triton releases it.
 pub struct RequestDataHolder {
-    _request: Box<infer_proto::ModelInferRequest>,
+    _request: Option<Box<infer_proto::ModelInferRequest>>,
     _bytes: Vec<Vec<u8>>,
+    _regions: Vec<Arc<SharedMemoryRegion>>,
 }
 
 impl RequestDataHolder {
-    pub fn new(request: Box<infer_proto::ModelInferRequest>, bytes: Vec<Vec<u8>>) -> Self {
-        RequestDataHolder { _request: request, _bytes: bytes }
+    pub fn new(
+        request: Box<infer_proto::ModelInferRequest>,
+        bytes: Vec<Vec<u8>>,
+        regions: Vec<Arc<SharedMemoryRegion>>,
+    ) -> Self {
+        RequestDataHolder {
+            _request: Some(request),
+            _bytes: bytes,
+            _regions: regions,
+        }
+    }
+
+    pub fn from_bytes(bytes: Vec<Vec<u8>>) -> Self {
+        RequestDataHolder {
+            _request: None,
+            _bytes: bytes,
+            _regions: vec![],
+        }
+    }
+}
+
+// userp of the response callback, the allocator gets a pointer to `output_buffers`
+struct ResponseContext {
+    sender: UnboundedSender<InferResponse>,
+    output_buffers: OutputBuffers,
+}
+
+// Runs on Triton's threads, so it must never wait for the receiver: the
+// channel is unbounded and responses queue up until they are read. The
+// context lives until the final callback, which frees it and closes the
+// channel.
+extern "C" fn response_callback(
+    response: *mut TRITONSERVER_InferenceResponse,
+    flags: u32,
+    userp: *mut c_void,
+) {
+    // borrow the channel, decoupled models call back once per response
+    let tx = unsafe { &(*(userp as *const ResponseContext)).sender };
+    if !response.is_null() {
+        // without a receiver the response is deleted right away
+        if tx.send(InferResponse::from_ptr(response)).is_err() {
+            error!("error sending response: receiver dropped");
+        }
     }
+    if flags & tritonserver_responsecompleteflag_enum_TRITONSERVER_RESPONSE_COMPLETE_FINAL != 0 {
+        let _context = unsafe { Box::from_raw(userp as *mut ResponseContext) };
+    }
+}
+
+/// Correlation ID of a request, which identifies its sequence for the
+/// sequence batcher.
+#[derive(Debug, Clone, PartialEq)]
+pub enum CorrelationId {
+    Int(u64),
+    String(String),
 }
 
+/// An inference request that has not been submitted yet. Dropping it deletes
+/// the Triton request together with the data and channel of its callbacks.
+/// `Server::infer_async` consumes it, after which Triton owns the request
+/// until the release callback fires.
 #[derive(Debug)]
 pub struct InferRequest {
     _request: *mut TRITONSERVER_InferenceRequest,
-    pub receiver: Option<Receiver<InferResponse>>,
+    // userp of the callbacks, reclaimed on drop if the request is never submitted
+    release_userp: *mut RequestDataHolder,
+    response_userp: *mut ResponseContext,
+    receiver: Option<UnboundedReceiver<InferResponse>>,
+    trace: Option<InferenceTrace>,
     pub raw_output: bool,
+    /// Number of classes to return for each output requested as a classification.
+    pub classification: HashMap<String, usize>,
+}
+
+/// A request handed to Triton, its responses arrive on `receiver`. Dropping
+/// it only stops listening, Triton still releases the request and its data.
+#[derive(Debug)]
+pub struct SubmittedRequest {
+    pub receiver: Option<UnboundedReceiver<InferResponse>>,
+    pub raw_output: bool,
+    pub classification: HashMap<String, usize>,
 }
 
 impl InferRequest {
@@ -37,7 +116,7 @@ impl InferRequest {
         raw_output: bool,
     ) -> Result<Self, TritonError> {
         let mut request = ptr::null_mut() as *mut TRITONSERVER_InferenceRequest;
-        let c_model_name = std::ffi::CString::new(model_name).unwrap();
+        let c_model_name = c_string("model name", model_name)?;
         let err = unsafe {
             TRITONSERVER_InferenceRequestNew(
                 &mut request as *mut *mut TRITONSERVER_InferenceRequest,
@@ -51,8 +130,12 @@ impl InferRequest {
         }
         Ok(InferRequest {
             _request: request,
+            release_userp: ptr::null_mut(),
+            response_userp: ptr::null_mut(),
             receiver: None,
+            trace: None,
             raw_output,
+            classification: HashMap::new(),
         })
     }
 
@@ -70,7 +153,7 @@ impl InferRequest {
     }
 
     pub fn set_id(&mut self, id: &str) -> Result<(), TritonError> {
-        let c_id = std::ffi::CString::new(id).unwrap();
+        let c_id = c_string("request id", id)?;
         let err = unsafe { TRITONSERVER_InferenceRequestSetId(self._request, c_id.as_ptr()) };
         if !err.is_null() {
             return Err(TritonError::from_ptr(err));
@@ -126,6 +209,36 @@ impl InferRequest {
         Ok(correlation_id)
     }
 
+    pub fn set_correlation_id(&mut self, correlation_id: u64) -> Result<(), TritonError> {
+        let err =
+            unsafe { TRITONSERVER_InferenceRequestSetCorrelationId(self._request, correlation_id) };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    pub fn set_correlation_id_string(&mut self, correlation_id: &str) -> Result<(), TritonError> {
+        let c_correlation_id = c_string("correlation id", correlation_id)?;
+        let err = unsafe {
+            TRITONSERVER_InferenceRequestSetCorrelationIdString(
+                self._request,
+                c_correlation_id.as_ptr(),
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    pub fn set_correlation(&mut self, correlation_id: &CorrelationId) -> Result<(), TritonError> {
+        match correlation_id {
+            CorrelationId::Int(id) => self.set_correlation_id(*id),
+            CorrelationId::String(id) => self.set_correlation_id_string(id),
+        }
+    }
+
     pub fn priority(&self) -> Result<u32, TritonError> {
         let mut priority = 0u32;
         let err = unsafe {
@@ -174,7 +287,7 @@ impl InferRequest {
         datatype: TRITONSERVER_DataType,
         shape: &[i64],
     ) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
         let dim_count = shape.len() as u64;
         let shape = shape.as_ptr();
 
@@ -194,7 +307,7 @@ impl InferRequest {
     }
 
     pub fn add_requested_output(&self, name: &str) -> Result<(), TritonError> {
-        let c_name = CString::new(name).unwrap();
+        let c_name = c_string("output name", name)?;
         let err = unsafe {
             TRITONSERVER_InferenceRequestAddRequestedOutput(self._request, c_name.as_ptr())
         };
@@ -205,7 +318,7 @@ impl InferRequest {
     }
 
     pub fn add_raw_input(&mut self, name: &str) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
         let err =
             unsafe { TRITONSERVER_InferenceRequestAddRawInput(self._request, c_name.as_ptr()) };
         if !err.is_null() {
@@ -215,7 +328,7 @@ impl InferRequest {
     }
 
     pub fn remove_input(&mut self, name: &str) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
         let err =
             unsafe { TRITONSERVER_InferenceRequestRemoveInput(self._request, c_name.as_ptr()) };
         if !err.is_null() {
@@ -232,7 +345,11 @@ impl InferRequest {
         Ok(())
     }
 
-    unsafe fn append_input_data(
+    /// # Safety
+    ///
+    /// `base` must point to `byte_size` readable bytes that stay valid until
+    /// the request is released.
+    pub unsafe fn append_input_data(
         &mut self,
         name: &str,
         base: *const c_void,
@@ -240,7 +357,7 @@ impl InferRequest {
         memory_type: TRITONSERVER_MemoryType,
         memory_type_id: i64,
     ) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
         let err = unsafe {
             TRITONSERVER_InferenceRequestAppendInputData(
                 self._request,
@@ -260,12 +377,12 @@ impl InferRequest {
     pub fn append_input_data_generic1<T>(
         &mut self,
         name: &str,
-        data: &[T] ,
+        data: &[T],
         byte_size: usize,
         memory_type: TRITONSERVER_MemoryType,
         memory_type_id: i64,
     ) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
 
         let err = unsafe {
             let base = data.as_ptr() as *const c_void;
@@ -284,16 +401,15 @@ impl InferRequest {
         Ok(())
     }
 
-
     pub fn append_input_data_generic2<T>(
         &mut self,
         name: &str,
-        data: &[T] ,
+        data: &[T],
         dtype: TRITONSERVER_DataType,
         memory_type: TRITONSERVER_MemoryType,
         memory_type_id: i64,
     ) -> Result<(), TritonError> {
-        let c_name = std::ffi::CString::new(name).unwrap();
+        let c_name = c_string("input name", name)?;
         let byte_size = data.len() * utils::data_type_size(dtype);
         let err = unsafe {
             let base = data.as_ptr() as *const c_void;
@@ -312,13 +428,26 @@ impl InferRequest {
         Ok(())
     }
 
-
     pub fn raw_pointer(&self) -> *mut TRITONSERVER_InferenceRequest {
         self._request
     }
 
+    /// Traces the request once submitted.
+    pub fn set_trace(&mut self, trace: InferenceTrace) {
+        self.trace = Some(trace);
+    }
+
+    pub fn trace_pointer(&self) -> *mut TRITONSERVER_InferenceTrace {
+        match &self.trace {
+            Some(trace) => trace.raw_pointer(),
+            None => ptr::null_mut(),
+        }
+    }
+
+    /// Sets the callback that deletes the request and frees
+    /// `request_data_holder` once Triton releases the request.
     pub fn set_release_callback(
-        &self,
+        &mut self,
         request_data_holder: Box<RequestDataHolder>,
     ) -> Result<(), TritonError> {
         #[allow(unused_variables)]
@@ -351,6 +480,10 @@ impl InferRequest {
             let _request_data_holder = unsafe { Box::from_raw(ptr as *mut RequestDataHolder) };
             return Err(TritonError::from_ptr(err));
         }
+        let previous = std::mem::replace(&mut self.release_userp, ptr as *mut RequestDataHolder);
+        if !previous.is_null() {
+            let _request_data_holder = unsafe { Box::from_raw(previous) };
+        }
         Ok(())
     }
 
@@ -359,55 +492,66 @@ impl InferRequest {
     // check if the pointer has been set
     // design 2: pass in a future and then caller can wait for that future?
     // design 3: use rust channels,
+    /// Sets the callback that sends every response of the request to the
+    /// receiver handed out on submission.
     pub fn set_response_callback(
-        &self,
+        &mut self,
         response_allocator: &ResponseAllocator,
-        response_userp: Sender<InferResponse>,
+        output_buffers: OutputBuffers,
     ) -> Result<(), TritonError> {
-        #[no_mangle]
-        extern "C" fn response_callback(
-            response: *mut TRITONSERVER_InferenceResponse,
-            flags: u32,
-            userp: *mut c_void,
-        ) {
-            // convert userp to a channel
-            let tx = unsafe { Box::from_raw(userp as *mut Sender<InferResponse>) };
-            if !response.is_null() {
-                let infer_response = InferResponse::from_ptr(response);
-
-                match block_on((tx).send(infer_response)) {
-                    Ok(_) => {}
-                    Err(e) => error!("error sending response: {:?}", e),
-                };
-            }
-            if flags & tritonserver_responsecompleteflag_enum_TRITONSERVER_RESPONSE_COMPLETE_FINAL
-                != 0
-            {}
-        }
-        let response_userp: *mut Sender<InferResponse> = Box::into_raw(Box::new(response_userp));
-        let response_userp: *mut c_void = response_userp as *mut c_void;
+        let (sender, receiver) = mpsc::unbounded_channel();
+        let context = Box::into_raw(Box::new(ResponseContext {
+            sender,
+            output_buffers,
+        }));
+        let allocator_userp = unsafe { &mut (*context).output_buffers as *mut OutputBuffers };
+        let response_userp: *mut c_void = context as *mut c_void;
         let err = unsafe {
             TRITONSERVER_InferenceRequestSetResponseCallback(
                 self._request,
                 response_allocator.raw_pointer(),
-                ptr::null_mut(),
+                allocator_userp as *mut c_void,
                 Some(response_callback),
                 response_userp,
             )
         };
 
         if !err.is_null() {
-            let _sender = unsafe { Box::from_raw(response_userp as *mut Sender<InferResponse>) };
+            let _context = unsafe { Box::from_raw(context) };
             return Err(TritonError::from_ptr(err));
         }
+        let previous = std::mem::replace(&mut self.response_userp, context);
+        if !previous.is_null() {
+            let _context = unsafe { Box::from_raw(previous) };
+        }
+        self.receiver = Some(receiver);
         Ok(())
     }
 
-    pub fn set_response_receiver(&mut self, receiver: Receiver<InferResponse>) {
-        self.receiver = Some(receiver);
+    /// Hands the request over to Triton, which `TRITONSERVER_ServerInferAsync`
+    /// must have accepted. Nothing is freed on drop from then on.
+    pub(crate) fn into_submitted(mut self) -> SubmittedRequest {
+        self._request = ptr::null_mut();
+        self.release_userp = ptr::null_mut();
+        self.response_userp = ptr::null_mut();
+        if let Some(trace) = self.trace.take() {
+            trace.into_submitted();
+        }
+        SubmittedRequest {
+            receiver: self.receiver.take(),
+            raw_output: self.raw_output,
+            classification: std::mem::take(&mut self.classification),
+        }
     }
+}
+unsafe impl Send for InferRequest {}
 
-    pub fn delete(&mut self) {
+impl Drop for InferRequest {
+    fn drop(&mut self) {
+        if self._request.is_null() {
+            return;
+        }
+        // never submitted, so the release callback will not fire
         let err = unsafe { TRITONSERVER_InferenceRequestDelete(self._request) };
         if !err.is_null() {
             error!(
@@ -415,16 +559,185 @@ impl InferRequest {
                 TritonError::from_ptr(err).msg()
             );
         }
+        if !self.release_userp.is_null() {
+            let _request_data_holder = unsafe { Box::from_raw(self.release_userp) };
+        }
+        if !self.response_userp.is_null() {
+            let _context = unsafe { Box::from_raw(self.response_userp) };
+        }
     }
 }
-unsafe impl Send for InferRequest {}
 
-/*
-impl Drop for InferRequest {
-    fn drop(&mut self) {
-        unsafe {
-            TRITONSERVER_InferenceRequestDelete(self._request);
+/// Builds an `InferRequest` that owns its input data.
+#[derive(Debug, Clone)]
+pub struct InferRequestBuilder {
+    model_name: String,
+    model_version: i64,
+    id: Option<String>,
+    inputs: Vec<InputTensor>,
+    outputs: Vec<String>,
+    flags: u32,
+    priority: Option<u32>,
+    timeout: Option<u64>,
+    correlation_id: Option<CorrelationId>,
+}
+
+#[derive(Debug, Clone)]
+struct InputTensor {
+    name: String,
+    datatype: TRITONSERVER_DataType,
+    shape: Vec<i64>,
+    data: Vec<u8>,
+}
+
+impl InferRequestBuilder {
+    /// Starts a request for the latest version of `model_name`.
+    pub fn new(model_name: &str) -> Self {
+        InferRequestBuilder {
+            model_name: model_name.to_owned(),
+            model_version: -1,
+            id: None,
+            inputs: vec![],
+            outputs: vec![],
+            flags: 0,
+            priority: None,
+            timeout: None,
+            correlation_id: None,
+        }
+    }
+
+    pub fn version(mut self, model_version: i64) -> Self {
+        self.model_version = model_version;
+        self
+    }
+
+    pub fn id(mut self, id: &str) -> Self {
+        self.id = Some(id.to_owned());
+        self
+    }
+
+    /// Adds an input whose `data` holds the tensor in Triton's layout, BYTES
+    /// elements each prefixed by their 4-byte little-endian length.
+    pub fn input(
+        mut self,
+        name: &str,
+        datatype: TRITONSERVER_DataType,
+        shape: &[i64],
+        data: Vec<u8>,
+    ) -> Self {
+        self.inputs.push(InputTensor {
+            name: name.to_owned(),
+            datatype,
+            shape: shape.to_vec(),
+            data,
+        });
+        self
+    }
+
+    /// Requests output `name`, the model returns all of its outputs when
+    /// none is requested.
+    pub fn output(mut self, name: &str) -> Self {
+        self.outputs.push(name.to_owned());
+        self
+    }
+
+    pub fn flags(mut self, flags: u32) -> Self {
+        self.flags = flags;
+        self
+    }
+
+    pub fn priority(mut self, priority: u32) -> Self {
+        self.priority = Some(priority);
+        self
+    }
+
+    pub fn timeout_micro_seconds(mut self, timeout: u64) -> Self {
+        self.timeout = Some(timeout);
+        self
+    }
+
+    pub fn correlation_id(mut self, correlation_id: CorrelationId) -> Self {
+        self.correlation_id = Some(correlation_id);
+        self
+    }
+
+    /// Creates the request on `server`, ready for `Server::infer_async`.
+    pub fn build(self, server: &Server) -> Result<InferRequest, TritonError> {
+        let mut bytes = Vec::with_capacity(self.inputs.len());
+        let mut request = InferRequest::new(server, &self.model_name, self.model_version, true)?;
+        if let Some(id) = &self.id {
+            request.set_id(id)?;
+        }
+        if self.flags != 0 {
+            request.set_flags(self.flags)?;
+        }
+        if let Some(priority) = self.priority {
+            request.set_priority(priority)?;
+        }
+        if let Some(timeout) = self.timeout {
+            request.set_timeout_micro_seconds(timeout)?;
+        }
+        if let Some(correlation_id) = &self.correlation_id {
+            request.set_correlation(correlation_id)?;
+        }
+        for input in self.inputs {
+            request.add_input(&input.name, input.datatype, &input.shape)?;
+            // the heap buffer does not move with the vector, it is freed on release
+            unsafe {
+                request.append_input_data(
+                    &input.name,
+                    input.data.as_ptr() as *const c_void,
+                    input.data.len(),
+                    TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
+                    0,
+                )?;
+            }
+            bytes.push(input.data);
+        }
+        for output in &self.outputs {
+            request.add_requested_output(output)?;
+        }
+        request.set_release_callback(Box::new(RequestDataHolder::from_bytes(bytes)))?;
+        request.set_response_callback(&server.response_allocator, OutputBuffers::default())?;
+        Ok(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_response_callback() {
+        let (sender, mut receiver) = mpsc::unbounded_channel();
+        let context = Box::into_raw(Box::new(ResponseContext {
+            sender,
+            output_buffers: OutputBuffers::default(),
+        }));
+        // decoupled models may call back without a response before the final flag
+        let threads: Vec<_> = (0..64)
+            .map(|_| {
+                let userp = context as usize;
+                std::thread::spawn(move || {
+                    response_callback(ptr::null_mut(), 0, userp as *mut c_void)
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
         }
+        // still open, nothing was sent
+        assert!(matches!(
+            receiver.try_recv(),
+            Err(mpsc::error::TryRecvError::Empty)
+        ));
+
+        // the final callback frees the context, closing the channel
+        response_callback(
+            ptr::null_mut(),
+            tritonserver_responsecompleteflag_enum_TRITONSERVER_RESPONSE_COMPLETE_FINAL,
+            context as *mut c_void,
+        );
+        assert!(receiver.blocking_recv().is_none());
     }
 }
-*/
@@ -1,4 +1,5 @@
-use super::error::TritonError;
+use super::error::{Result, RustisError, TritonError};
+use super::parameter::ParameterValue;
 use super::utils;
 use crate::sys::*;
 use std::ffi::c_char;
@@ -102,6 +103,93 @@ impl InferResponse {
             )
         }
     }
+
+    /// Returns the parameters set on the response by the backend, such as
+    /// `sequence_end` or `triton_final_response`. Fails with `Unsupported`
+    /// on bytes parameters, whose size the C API doesn't return.
+    #[allow(non_upper_case_globals)]
+    pub fn parameters(&self) -> Result<Vec<(String, ParameterValue)>> {
+        let mut count = 0;
+        let err =
+            unsafe { TRITONSERVER_InferenceResponseParameterCount(self._response, &mut count) };
+        if !err.is_null() {
+            return Err(RustisError::from(TritonError::from_ptr(err)));
+        }
+        let mut parameters = Vec::with_capacity(count as usize);
+        for index in 0..count {
+            let mut name = ptr::null() as *const c_char;
+            let mut parameter_type = TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_STRING;
+            let mut value = ptr::null() as *const c_void;
+            let err = unsafe {
+                TRITONSERVER_InferenceResponseParameter(
+                    self._response,
+                    index,
+                    &mut name,
+                    &mut parameter_type,
+                    &mut value,
+                )
+            };
+            if !err.is_null() {
+                return Err(RustisError::from(TritonError::from_ptr(err)));
+            }
+            let name = unsafe { CStr::from_ptr(name) }
+                .to_string_lossy()
+                .into_owned();
+            let value = match parameter_type {
+                TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_BOOL => {
+                    ParameterValue::Bool(unsafe { *(value as *const bool) })
+                }
+                TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_INT => {
+                    ParameterValue::Int(unsafe { *(value as *const i64) })
+                }
+                TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_STRING => {
+                    ParameterValue::String(
+                        unsafe { CStr::from_ptr(value as *const c_char) }
+                            .to_string_lossy()
+                            .into_owned(),
+                    )
+                }
+                // the API returns no size for bytes values, Triton's own frontends refuse them too
+                _ => {
+                    return Err(RustisError::Unsupported(format!(
+                        "response parameter '{}' of type {} is not supported",
+                        name, parameter_type
+                    )))
+                }
+            };
+            parameters.push((name, value));
+        }
+        Ok(parameters)
+    }
+
+    /// Returns the label of class `class_index` of output `index`, from the
+    /// label file configured for the output in the model config.
+    pub fn output_classification_label(
+        &self,
+        index: u32,
+        class_index: usize,
+    ) -> Result<Option<String>, TritonError> {
+        let mut label = ptr::null() as *const c_char;
+        let err = unsafe {
+            TRITONSERVER_InferenceResponseOutputClassificationLabel(
+                self._response,
+                index,
+                class_index,
+                &mut label,
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        if label.is_null() {
+            return Ok(None);
+        }
+        Ok(Some(
+            unsafe { CStr::from_ptr(label) }
+                .to_string_lossy()
+                .into_owned(),
+        ))
+    }
 }
 //  uncomment causes a double free error  TODO: fix
 unsafe impl Send for InferResponse {}
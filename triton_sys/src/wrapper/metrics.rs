@@ -1,8 +1,44 @@
 use crate::sys::*;
+use std::ffi::c_char;
+use std::ptr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use log::error;
+
+use super::error::{c_string, TritonError};
+use super::parameter::Parameter;
+
+/// A snapshot of the server metrics returned by TRITONSERVER_ServerMetrics.
 pub struct TritonMetrics {
     _metrics: *mut TRITONSERVER_Metrics,
 }
 
+impl TritonMetrics {
+    pub fn from_ptr(ptr: *mut TRITONSERVER_Metrics) -> Self {
+        TritonMetrics { _metrics: ptr }
+    }
+
+    /// Returns the metrics in the Prometheus text exposition format.
+    pub fn formatted(&self) -> Result<String, TritonError> {
+        let mut base = ptr::null() as *const c_char;
+        let mut byte_size = 0;
+        let err = unsafe {
+            TRITONSERVER_MetricsFormatted(
+                self._metrics,
+                tritonserver_metricformat_enum_TRITONSERVER_METRIC_PROMETHEUS,
+                &mut base as *mut *const c_char,
+                &mut byte_size as *mut usize,
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        let buffer_slice = unsafe { std::slice::from_raw_parts(base as *const u8, byte_size) };
+        Ok(String::from_utf8_lossy(buffer_slice).into_owned())
+    }
+}
+
 impl Drop for TritonMetrics {
     fn drop(&mut self) {
         unsafe {
@@ -10,3 +46,168 @@ impl Drop for TritonMetrics {
         }
     }
 }
+
+unsafe impl Send for TritonMetrics {}
+
+/// Direction of a conversion between protobuf messages and Triton objects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Conversion {
+    Request,
+    Response,
+}
+
+impl Conversion {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Conversion::Request => "request",
+            Conversion::Response => "response",
+        }
+    }
+}
+
+/// Receives the time spent on every protobuf conversion made by the server.
+pub type ConversionObserver = Box<dyn Fn(Conversion, Duration) + Send + Sync>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricKind {
+    Counter,
+    Gauge,
+}
+
+impl MetricKind {
+    fn raw(&self) -> TRITONSERVER_MetricKind {
+        match self {
+            MetricKind::Counter => TRITONSERVER_metrickind_enum_TRITONSERVER_METRIC_KIND_COUNTER,
+            MetricKind::Gauge => TRITONSERVER_metrickind_enum_TRITONSERVER_METRIC_KIND_GAUGE,
+        }
+    }
+}
+
+/// A custom metric family published alongside Triton's own metrics. Its
+/// metrics keep it alive, Triton refuses to delete a family that still
+/// has metrics.
+pub struct MetricFamily {
+    _family: *mut TRITONSERVER_MetricFamily,
+    kind: MetricKind,
+}
+
+impl MetricFamily {
+    pub fn new(kind: MetricKind, name: &str, description: &str) -> Result<Arc<Self>, TritonError> {
+        let c_name = c_string("metric family name", name)?;
+        let c_description = c_string("metric family description", description)?;
+        let mut family: *mut TRITONSERVER_MetricFamily = ptr::null_mut();
+        let err = unsafe {
+            TRITONSERVER_MetricFamilyNew(
+                &mut family,
+                kind.raw(),
+                c_name.as_ptr(),
+                c_description.as_ptr(),
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(Arc::new(MetricFamily {
+            _family: family,
+            kind,
+        }))
+    }
+
+    pub fn kind(&self) -> MetricKind {
+        self.kind
+    }
+
+    /// Creates the metric of this family identified by `labels`.
+    pub fn metric(self: &Arc<Self>, labels: &[(&str, &str)]) -> Result<Metric, TritonError> {
+        // labels are copied by Triton and can be released once the metric exists
+        let labels: Vec<Parameter> = labels
+            .iter()
+            .map(|(name, value)| Parameter::from_string(name, value))
+            .collect::<Result<_, _>>()?;
+        let mut raw_labels: Vec<*const TRITONSERVER_Parameter> = labels
+            .iter()
+            .map(|l| l.raw_pointer() as *const TRITONSERVER_Parameter)
+            .collect();
+        let mut metric: *mut TRITONSERVER_Metric = ptr::null_mut();
+        let err = unsafe {
+            TRITONSERVER_MetricNew(
+                &mut metric,
+                self._family,
+                raw_labels.as_mut_ptr(),
+                raw_labels.len() as u64,
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(Metric {
+            _metric: metric,
+            _family: self.clone(),
+        })
+    }
+}
+
+impl Drop for MetricFamily {
+    fn drop(&mut self) {
+        let err = unsafe { TRITONSERVER_MetricFamilyDelete(self._family) };
+        if !err.is_null() {
+            error!(
+                "failed to delete metric family: {}",
+                TritonError::from_ptr(err)
+            );
+        }
+    }
+}
+
+unsafe impl Send for MetricFamily {}
+unsafe impl Sync for MetricFamily {}
+
+/// A single counter or gauge of a `MetricFamily`. Counters only support
+/// non-negative increments, `set` is only supported by gauges.
+pub struct Metric {
+    _metric: *mut TRITONSERVER_Metric,
+    _family: Arc<MetricFamily>,
+}
+
+impl Metric {
+    pub fn kind(&self) -> MetricKind {
+        self._family.kind()
+    }
+
+    pub fn value(&self) -> Result<f64, TritonError> {
+        let mut value = 0.0;
+        let err = unsafe { TRITONSERVER_MetricValue(self._metric, &mut value) };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(value)
+    }
+
+    pub fn increment(&self, value: f64) -> Result<(), TritonError> {
+        let err = unsafe { TRITONSERVER_MetricIncrement(self._metric, value) };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    pub fn set(&self, value: f64) -> Result<(), TritonError> {
+        let err = unsafe { TRITONSERVER_MetricSet(self._metric, value) };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+}
+
+impl Drop for Metric {
+    fn drop(&mut self) {
+        let err = unsafe { TRITONSERVER_MetricDelete(self._metric) };
+        if !err.is_null() {
+            error!("failed to delete metric: {}", TritonError::from_ptr(err));
+        }
+    }
+}
+
+unsafe impl Send for Metric {}
+unsafe impl Sync for Metric {}
@@ -1,14 +1,21 @@
 // TODO: better error handling: error-stack?
+pub mod classification;
 pub mod error;
 pub mod inference_request;
 pub mod inference_response;
 pub mod message;
-// TODO: add prometheus metrics publishing
 pub mod metrics;
+pub mod model_config;
+pub mod model_index;
+pub mod model_properties;
+pub mod parameter;
 // TODO: learn from cpp code, try to avoid copy when creating rpc server responses
 pub mod response_allocator;
 pub mod server;
-// TODO: add more server options support
 pub mod server_options;
+pub mod shared_memory;
+pub mod statistics;
+pub mod tensor;
+pub mod trace;
 pub mod utils;
 // TODO: s3 download / model registry support
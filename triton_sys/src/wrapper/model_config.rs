@@ -0,0 +1,273 @@
+use std::collections::HashMap;
+
+use infer_proto::infer_proto;
+use serde::{Deserialize, Deserializer};
+
+/// The effective configuration of a model as returned by
+/// TRITONSERVER_ServerModelConfig. Only the fields needed by clients to
+/// discover how a model can be called are kept.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelConfig {
+    pub name: String,
+    pub platform: String,
+    pub backend: String,
+    pub max_batch_size: i32,
+    pub input: Vec<ModelInput>,
+    pub output: Vec<ModelOutput>,
+    pub instance_group: Vec<ModelInstanceGroup>,
+    pub default_model_filename: String,
+    pub dynamic_batching: Option<ModelDynamicBatching>,
+    pub sequence_batching: Option<ModelSequenceBatching>,
+    pub parameters: HashMap<String, ModelParameter>,
+    pub model_transaction_policy: Option<ModelTransactionPolicy>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelInput {
+    pub name: String,
+    /// Model configuration data type, e.g. "TYPE_FP32".
+    pub data_type: String,
+    pub format: String,
+    #[serde(deserialize_with = "deserialize_dims")]
+    pub dims: Vec<i64>,
+    pub is_shape_tensor: bool,
+    pub allow_ragged_batch: bool,
+    pub optional: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelOutput {
+    pub name: String,
+    /// Model configuration data type, e.g. "TYPE_FP32".
+    pub data_type: String,
+    #[serde(deserialize_with = "deserialize_dims")]
+    pub dims: Vec<i64>,
+    pub label_filename: String,
+    pub is_shape_tensor: bool,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelInstanceGroup {
+    pub name: String,
+    /// Instance group kind, e.g. "KIND_GPU".
+    pub kind: String,
+    pub count: i32,
+    pub gpus: Vec<i32>,
+    pub profile: Vec<String>,
+    pub passive: bool,
+    pub host_policy: String,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelDynamicBatching {
+    pub preferred_batch_size: Vec<i32>,
+    #[serde(deserialize_with = "deserialize_u64")]
+    pub max_queue_delay_microseconds: u64,
+    pub preserve_ordering: bool,
+    pub priority_levels: u32,
+    pub default_priority_level: u32,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelSequenceBatching {
+    #[serde(deserialize_with = "deserialize_u64")]
+    pub max_sequence_idle_microseconds: u64,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelParameter {
+    pub string_value: String,
+}
+
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct ModelTransactionPolicy {
+    pub decoupled: bool,
+}
+
+// protobuf's JSON mapping writes 64 bit integers as strings, accept both forms
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum JsonInt {
+    Number(i64),
+    String(String),
+}
+
+impl JsonInt {
+    fn value<E: serde::de::Error>(self) -> Result<i64, E> {
+        match self {
+            JsonInt::Number(n) => Ok(n),
+            JsonInt::String(s) => s.parse::<i64>().map_err(E::custom),
+        }
+    }
+}
+
+fn deserialize_dims<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<i64>, D::Error> {
+    Vec::<JsonInt>::deserialize(deserializer)?
+        .into_iter()
+        .map(JsonInt::value)
+        .collect()
+}
+
+fn deserialize_u64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<u64, D::Error> {
+    let value = JsonInt::deserialize(deserializer)?.value()?;
+    u64::try_from(value).map_err(serde::de::Error::custom)
+}
+
+impl ModelConfig {
+    pub fn from_json(json: &str) -> serde_json::Result<Self> {
+        serde_json::from_str(json)
+    }
+
+    pub fn is_decoupled(&self) -> bool {
+        self.model_transaction_policy
+            .map(|p| p.decoupled)
+            .unwrap_or(false)
+    }
+}
+
+fn data_type_value(data_type: &str) -> i32 {
+    infer_proto::DataType::from_str_name(data_type).unwrap_or(infer_proto::DataType::TypeInvalid)
+        as i32
+}
+
+impl From<ModelInput> for infer_proto::ModelInput {
+    fn from(input: ModelInput) -> Self {
+        let format = infer_proto::model_input::Format::from_str_name(&input.format)
+            .unwrap_or(infer_proto::model_input::Format::None);
+        infer_proto::ModelInput {
+            name: input.name,
+            data_type: data_type_value(&input.data_type),
+            format: format as i32,
+            dims: input.dims,
+            is_shape_tensor: input.is_shape_tensor,
+            allow_ragged_batch: input.allow_ragged_batch,
+            optional: input.optional,
+        }
+    }
+}
+
+impl From<ModelOutput> for infer_proto::ModelOutput {
+    fn from(output: ModelOutput) -> Self {
+        infer_proto::ModelOutput {
+            name: output.name,
+            data_type: data_type_value(&output.data_type),
+            dims: output.dims,
+            label_filename: output.label_filename,
+            is_shape_tensor: output.is_shape_tensor,
+        }
+    }
+}
+
+impl From<ModelInstanceGroup> for infer_proto::ModelInstanceGroup {
+    fn from(group: ModelInstanceGroup) -> Self {
+        let kind = infer_proto::model_instance_group::Kind::from_str_name(&group.kind)
+            .unwrap_or(infer_proto::model_instance_group::Kind::Auto);
+        infer_proto::ModelInstanceGroup {
+            name: group.name,
+            count: group.count,
+            gpus: group.gpus,
+            kind: kind as i32,
+            profile: group.profile,
+            passive: group.passive,
+            host_policy: group.host_policy,
+        }
+    }
+}
+
+impl From<ModelConfig> for infer_proto::ModelConfig {
+    fn from(config: ModelConfig) -> Self {
+        let scheduling_choice = match (config.dynamic_batching, config.sequence_batching) {
+            (Some(d), _) => Some(
+                infer_proto::model_config::SchedulingChoice::DynamicBatching(
+                    infer_proto::ModelDynamicBatching {
+                        preferred_batch_size: d.preferred_batch_size,
+                        max_queue_delay_microseconds: d.max_queue_delay_microseconds,
+                        preserve_ordering: d.preserve_ordering,
+                        priority_levels: d.priority_levels,
+                        default_priority_level: d.default_priority_level,
+                    },
+                ),
+            ),
+            (None, Some(s)) => Some(
+                infer_proto::model_config::SchedulingChoice::SequenceBatching(
+                    infer_proto::ModelSequenceBatching {
+                        max_sequence_idle_microseconds: s.max_sequence_idle_microseconds,
+                    },
+                ),
+            ),
+            (None, None) => None,
+        };
+        infer_proto::ModelConfig {
+            name: config.name,
+            platform: config.platform,
+            max_batch_size: config.max_batch_size,
+            input: config.input.into_iter().map(Into::into).collect(),
+            output: config.output.into_iter().map(Into::into).collect(),
+            instance_group: config.instance_group.into_iter().map(Into::into).collect(),
+            default_model_filename: config.default_model_filename,
+            scheduling_choice,
+            parameters: config
+                .parameters
+                .into_iter()
+                .map(|(k, v)| {
+                    let parameter = infer_proto::ModelParameter {
+                        string_value: v.string_value,
+                    };
+                    (k, parameter)
+                })
+                .collect(),
+            backend: config.backend,
+            model_transaction_policy: config.model_transaction_policy.map(|p| {
+                infer_proto::ModelTransactionPolicy {
+                    decoupled: p.decoupled,
+                }
+            }),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_config() {
+        let json = r#"{"name":"addsub","platform":"","backend":"python","max_batch_size":8,
+            "input":[{"name":"INPUT0","data_type":"TYPE_FP32","format":"FORMAT_NONE","dims":[4]}],
+            "output":[{"name":"OUTPUT0","data_type":"TYPE_FP32","dims":["4"],"label_filename":""}],
+            "instance_group":[{"name":"addsub_0","kind":"KIND_CPU","count":1,"gpus":[]}],
+            "dynamic_batching":{"preferred_batch_size":[4],"max_queue_delay_microseconds":"100"},
+            "model_transaction_policy":{"decoupled":false}}"#;
+        let config = ModelConfig::from_json(json).unwrap();
+        assert_eq!(config.max_batch_size, 8);
+        assert_eq!(config.input[0].dims, vec![4]);
+        assert_eq!(config.output[0].dims, vec![4]);
+        assert_eq!(
+            config
+                .dynamic_batching
+                .as_ref()
+                .unwrap()
+                .max_queue_delay_microseconds,
+            100
+        );
+        assert!(!config.is_decoupled());
+
+        let pb_config: infer_proto::ModelConfig = config.into();
+        assert_eq!(
+            pb_config.input[0].data_type,
+            infer_proto::DataType::TypeFp32 as i32
+        );
+        assert_eq!(
+            pb_config.instance_group[0].kind,
+            infer_proto::model_instance_group::Kind::Cpu as i32
+        );
+    }
+}
@@ -0,0 +1,91 @@
+use infer_proto::infer_proto::repository_index_response::ModelIndex;
+use serde::Deserialize;
+
+/// State of a model version in the repository index.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum ModelState {
+    Ready,
+    Unavailable,
+    Loading,
+    Unloading,
+    /// Reported for models that have never been loaded, and for states this
+    /// wrapper does not know.
+    #[default]
+    #[serde(other)]
+    Unknown,
+}
+
+impl ModelState {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ModelState::Ready => "READY",
+            ModelState::Unavailable => "UNAVAILABLE",
+            ModelState::Loading => "LOADING",
+            ModelState::Unloading => "UNLOADING",
+            ModelState::Unknown => "UNKNOWN",
+        }
+    }
+}
+
+/// One entry of TRITONSERVER_ServerModelIndex. Models that were never loaded
+/// have no version nor state.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Deserialize)]
+#[serde(default)]
+pub struct ModelIndexEntry {
+    pub name: String,
+    pub version: String,
+    pub state: ModelState,
+    /// Why the model is unavailable, such as a load failure.
+    pub reason: String,
+}
+
+pub fn parse_model_index(json: &str) -> serde_json::Result<Vec<ModelIndexEntry>> {
+    serde_json::from_str(json)
+}
+
+impl From<ModelIndexEntry> for ModelIndex {
+    fn from(entry: ModelIndexEntry) -> Self {
+        // never loaded models have an empty state, as in Triton's frontends
+        let state = match (entry.state, entry.version.is_empty()) {
+            (ModelState::Unknown, true) => String::new(),
+            (state, _) => state.as_str().to_owned(),
+        };
+        ModelIndex {
+            name: entry.name,
+            version: entry.version,
+            state,
+            reason: entry.reason,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_index() {
+        let json = r#"[{"name":"addsub","version":"1","state":"READY"},
+            {"name":"broken","version":"1","state":"UNAVAILABLE","reason":"load failed"},
+            {"name":"idle"},
+            {"name":"future","version":"2","state":"DRAINING"}]"#;
+        let index = parse_model_index(json).unwrap();
+        assert_eq!(index.len(), 4);
+        assert_eq!(index[0].state, ModelState::Ready);
+        assert_eq!(index[1].reason, "load failed");
+        assert_eq!(
+            index[2],
+            ModelIndexEntry {
+                name: "idle".to_string(),
+                ..Default::default()
+            }
+        );
+        assert_eq!(index[3].state, ModelState::Unknown);
+
+        let pb: Vec<ModelIndex> = index.into_iter().map(Into::into).collect();
+        assert_eq!(pb[1].state, "UNAVAILABLE");
+        assert_eq!(pb[2].state, "");
+        assert_eq!(pb[3].state, "UNKNOWN");
+    }
+}
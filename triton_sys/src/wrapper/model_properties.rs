@@ -0,0 +1,91 @@
+use crate::sys::*;
+
+use super::error::RustisError;
+
+/// How a model batches, from TRITONSERVER_ServerModelBatchProperties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BatchProperties {
+    /// The model does not batch in a way Triton can use.
+    Unknown,
+    /// Requests can be batched along the first dimension of every input and
+    /// output.
+    FirstDim,
+}
+
+impl TryFrom<u32> for BatchProperties {
+    type Error = RustisError;
+
+    #[allow(non_upper_case_globals)]
+    fn try_from(flags: u32) -> Result<Self, Self::Error> {
+        match flags {
+            tritonserver_batchflag_enum_TRITONSERVER_BATCH_UNKNOWN => Ok(BatchProperties::Unknown),
+            tritonserver_batchflag_enum_TRITONSERVER_BATCH_FIRST_DIM => {
+                Ok(BatchProperties::FirstDim)
+            }
+            _ => Err(RustisError::Unsupported(format!(
+                "unknown model batch flags {}",
+                flags
+            ))),
+        }
+    }
+}
+
+/// How many responses a model sends per request, from
+/// TRITONSERVER_ServerModelTransactionProperties.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionPolicy {
+    /// Exactly one response per request.
+    OneToOne,
+    /// Any number of responses per request, including none.
+    Decoupled,
+}
+
+impl TryFrom<u32> for TransactionPolicy {
+    type Error = RustisError;
+
+    #[allow(non_upper_case_globals)]
+    fn try_from(flags: u32) -> Result<Self, Self::Error> {
+        match flags {
+            tritonserver_txn_property_flag_enum_TRITONSERVER_TXN_ONE_TO_ONE => {
+                Ok(TransactionPolicy::OneToOne)
+            }
+            tritonserver_txn_property_flag_enum_TRITONSERVER_TXN_DECOUPLED => {
+                Ok(TransactionPolicy::Decoupled)
+            }
+            _ => Err(RustisError::Unsupported(format!(
+                "unknown model transaction flags {}",
+                flags
+            ))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_properties_from_flags() {
+        assert_eq!(
+            BatchProperties::try_from(1).unwrap(),
+            BatchProperties::Unknown
+        );
+        assert_eq!(
+            BatchProperties::try_from(2).unwrap(),
+            BatchProperties::FirstDim
+        );
+        assert!(BatchProperties::try_from(3).is_err());
+        assert_eq!(
+            TransactionPolicy::try_from(1).unwrap(),
+            TransactionPolicy::OneToOne
+        );
+        assert_eq!(
+            TransactionPolicy::try_from(2).unwrap(),
+            TransactionPolicy::Decoupled
+        );
+        assert!(matches!(
+            TransactionPolicy::try_from(0),
+            Err(RustisError::Unsupported(_))
+        ));
+    }
+}
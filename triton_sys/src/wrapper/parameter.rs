@@ -0,0 +1,104 @@
+use std::ffi::c_void;
+
+use super::error::{c_string, TritonError};
+use crate::sys::*;
+
+/// Value of a parameter read back from Triton. There is no bytes variant,
+/// Triton doesn't return the size of bytes values.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ParameterValue {
+    Bool(bool),
+    Int(i64),
+    String(String),
+}
+
+/// A named value passed to Triton, e.g. a model load override or a metric
+/// label. Creation fails on names or strings with NUL bytes.
+pub struct Parameter {
+    _parameter: *mut TRITONSERVER_Parameter,
+    // TRITONSERVER_ParameterBytesNew only keeps a shallow copy of the content,
+    // so the bytes have to live as long as the parameter object
+    _bytes: Option<Vec<u8>>,
+}
+
+impl Parameter {
+    fn new(
+        name: &str,
+        parameter_type: TRITONSERVER_ParameterType,
+        value: *const c_void,
+    ) -> Result<Self, TritonError> {
+        let c_name = c_string("parameter name", name)?;
+        let parameter =
+            unsafe { TRITONSERVER_ParameterNew(c_name.as_ptr(), parameter_type, value) };
+        Parameter::from_ptr(name, parameter, None)
+    }
+
+    fn from_ptr(
+        name: &str,
+        parameter: *mut TRITONSERVER_Parameter,
+        bytes: Option<Vec<u8>>,
+    ) -> Result<Self, TritonError> {
+        if parameter.is_null() {
+            return Err(TritonError::new(
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INTERNAL,
+                format!("failed to create parameter '{}'", name),
+            ));
+        }
+        Ok(Parameter {
+            _parameter: parameter,
+            _bytes: bytes,
+        })
+    }
+
+    pub fn from_string(name: &str, value: &str) -> Result<Self, TritonError> {
+        // Triton copies string values
+        let c_value = c_string(&format!("value of parameter '{}'", name), value)?;
+        Parameter::new(
+            name,
+            TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_STRING,
+            c_value.as_ptr() as *const c_void,
+        )
+    }
+
+    pub fn from_int(name: &str, value: i64) -> Result<Self, TritonError> {
+        Parameter::new(
+            name,
+            TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_INT,
+            &value as *const i64 as *const c_void,
+        )
+    }
+
+    pub fn from_bool(name: &str, value: bool) -> Result<Self, TritonError> {
+        Parameter::new(
+            name,
+            TRITONSERVER_parametertype_enum_TRITONSERVER_PARAMETER_BOOL,
+            &value as *const bool as *const c_void,
+        )
+    }
+
+    pub fn from_bytes(name: &str, value: Vec<u8>) -> Result<Self, TritonError> {
+        let c_name = c_string("parameter name", name)?;
+        let parameter = unsafe {
+            TRITONSERVER_ParameterBytesNew(
+                c_name.as_ptr(),
+                value.as_ptr() as *const c_void,
+                value.len() as u64,
+            )
+        };
+        Parameter::from_ptr(name, parameter, Some(value))
+    }
+
+    pub fn raw_pointer(&self) -> *mut TRITONSERVER_Parameter {
+        self._parameter
+    }
+}
+
+impl Drop for Parameter {
+    fn drop(&mut self) {
+        unsafe {
+            TRITONSERVER_ParameterDelete(self._parameter);
+        }
+    }
+}
+
+unsafe impl Send for Parameter {}
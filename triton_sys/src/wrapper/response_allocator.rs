@@ -1,10 +1,38 @@
-use std::ffi::CString;
+use std::collections::HashMap;
+use std::ffi::{CStr, CString};
+use std::sync::Arc;
 use std::{alloc::Layout, ffi::c_void, ptr, u8};
 
 use crate::sys::*;
 use log::error;
 
 use super::error::TritonError;
+use super::shared_memory::SharedMemoryRegion;
+
+/// Memory an output tensor is written into instead of a heap allocation.
+#[derive(Debug, Clone)]
+pub struct OutputBuffer {
+    pub region: Arc<SharedMemoryRegion>,
+    pub offset: usize,
+    pub byte_size: usize,
+}
+
+/// Output buffers of a request keyed by tensor name, passed to the allocator
+/// as its userp.
+#[derive(Debug, Default)]
+pub struct OutputBuffers {
+    buffers: HashMap<String, OutputBuffer>,
+}
+
+impl OutputBuffers {
+    pub fn insert(&mut self, name: &str, buffer: OutputBuffer) {
+        self.buffers.insert(name.to_owned(), buffer);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&OutputBuffer> {
+        self.buffers.get(name)
+    }
+}
 
 pub struct ResponseAllocator {
     _allocator: *mut TRITONSERVER_ResponseAllocator,
@@ -30,6 +58,41 @@ impl ResponseAllocator {
             *actual_memory_type_id = 0;
             *buffer = ptr::null_mut();
             *buffer_userp = ptr::null_mut();
+            if !userp.is_null() {
+                let output_buffers = &*(userp as *const OutputBuffers);
+                let name = CStr::from_ptr(tensor_name).to_string_lossy();
+                if let Some(output_buffer) = output_buffers.get(&name) {
+                    if byte_size > output_buffer.byte_size {
+                        let msg = CString::new(format!(
+                            "shared memory size specified with the request for output '{}' \
+                             should be at least {} bytes to hold the results",
+                            name, byte_size
+                        ))
+                        .unwrap();
+                        return TRITONSERVER_ErrorNew(
+                            TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG,
+                            msg.as_ptr(),
+                        );
+                    }
+                    *buffer = match output_buffer
+                        .region
+                        .slice(output_buffer.offset, output_buffer.byte_size)
+                    {
+                        Ok(b) => b as *mut c_void,
+                        Err(e) => {
+                            let msg = CString::new(e.to_string()).unwrap();
+                            return TRITONSERVER_ErrorNew(
+                                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG,
+                                msg.as_ptr(),
+                            );
+                        }
+                    };
+                    // the buffer keeps the region mapped until the response is released
+                    let region = Box::new(output_buffer.region.clone());
+                    *buffer_userp = Box::into_raw(region) as *mut c_void;
+                    return ptr::null_mut();
+                }
+            }
             let layout_msg = CString::new("allocator failed to create layout").unwrap();
             let failed_msg = CString::new("allocator failed").unwrap();
             if byte_size != 0 {
@@ -63,6 +126,11 @@ impl ResponseAllocator {
             memory_type: TRITONSERVER_MemoryType,
             memory_type_id: i64,
         ) -> *mut TRITONSERVER_Error {
+            if !buffer_userp.is_null() {
+                // shared memory buffer, only the reference to the region is owned here
+                let _region = Box::from_raw(buffer_userp as *mut Arc<SharedMemoryRegion>);
+                return ptr::null_mut();
+            }
             let layout_msg = CString::new("allocator failed to create layout").unwrap();
             let layout = match Layout::from_size_align(byte_size, 8) {
                 Ok(l) => l,
@@ -1,29 +1,463 @@
 use std::{
-    collections::HashMap,
-    ffi::{c_void, CString},
+    collections::{HashMap, HashSet},
+    ffi::c_void,
     ptr::{self, null_mut},
+    sync::Mutex,
+    time::Instant,
 };
 
-use log::{error, info};
-
 use crate::sys::*;
-use anyhow::Result;
 use infer_proto::infer_proto;
 use tokio::sync::mpsc;
 
 use super::{
-    error::{InferError, TritonError},
-    inference_request::{InferRequest, RequestDataHolder},
+    classification::{self, Classification},
+    error::{c_string, Result, RustisError, TritonError},
+    inference_request::{
+        CorrelationId, InferRequest, InferRequestBuilder, RequestDataHolder, SubmittedRequest,
+    },
     inference_response::InferResponse,
     message::TritonMessage,
-    response_allocator::ResponseAllocator,
+    metrics::{Conversion, ConversionObserver, TritonMetrics},
+    model_config::ModelConfig,
+    model_index::{self, ModelIndexEntry},
+    model_properties::{BatchProperties, TransactionPolicy},
+    parameter::{Parameter, ParameterValue},
+    response_allocator::{OutputBuffer, OutputBuffers, ResponseAllocator},
     server_options::ServerOptions,
+    shared_memory::{SharedMemoryManager, SharedMemoryRegion},
+    statistics::{self, ModelStatistics},
+    tensor::Tensor,
+    trace::{TraceContext, Tracer},
     utils,
 };
 
 pub struct Server {
     pub _server: *mut TRITONSERVER_Server,
     pub response_allocator: ResponseAllocator,
+    pub shared_memory: SharedMemoryManager,
+    conversion_observer: Option<ConversionObserver>,
+    unknown_parameters: UnknownParameters,
+    decoupled_models: DecoupledModels,
+    /// Transaction policy of every model name and version `infer` has run,
+    /// cleared whenever models are loaded or unloaded.
+    transaction_policies: Mutex<HashMap<(String, i64), TransactionPolicy>>,
+    tracer: Option<Tracer>,
+}
+
+/// Location of a tensor in a registered shared memory region, given by the
+/// `shared_memory_region`, `shared_memory_offset` and `shared_memory_byte_size`
+/// tensor parameters.
+struct SharedMemoryParameters {
+    region: String,
+    offset: usize,
+    byte_size: Option<usize>,
+}
+
+impl SharedMemoryParameters {
+    fn parse(parameters: &HashMap<String, infer_proto::InferParameter>) -> Result<Option<Self>> {
+        let region = match parameters.get("shared_memory_region") {
+            Some(infer_proto::InferParameter {
+                parameter_choice: Some(infer_proto::infer_parameter::ParameterChoice::StringParam(r)),
+            }) => r.clone(),
+            Some(_) => {
+                return Err(RustisError::Validation(
+                    "invalid value type for 'shared_memory_region' parameter, expected string"
+                        .to_string(),
+                ))
+            }
+            None => return Ok(None),
+        };
+        let int_parameter = |key: &str| -> Result<Option<usize>> {
+            match parameters.get(key) {
+                Some(infer_proto::InferParameter {
+                    parameter_choice: Some(infer_proto::infer_parameter::ParameterChoice::Int64Param(v)),
+                }) if *v >= 0 => Ok(Some(*v as usize)),
+                Some(_) => Err(RustisError::Validation(format!(
+                    "invalid value for '{}' parameter, expected non-negative int64",
+                    key
+                ))),
+                None => Ok(None),
+            }
+        };
+        Ok(Some(SharedMemoryParameters {
+            region,
+            offset: int_parameter("shared_memory_offset")?.unwrap_or(0),
+            byte_size: int_parameter("shared_memory_byte_size")?,
+        }))
+    }
+
+    fn byte_size(&self, region: &SharedMemoryRegion) -> usize {
+        self.byte_size
+            .unwrap_or_else(|| region.byte_size().saturating_sub(self.offset))
+    }
+}
+
+/// Request parameters defined by Triton, inputs and outputs accept the
+/// shared memory and binary data ones plus `classification` for outputs.
+const REQUEST_PARAMETERS: &[&str] = &[
+    "priority",
+    "timeout",
+    "sequence_id",
+    "sequence_start",
+    "sequence_end",
+    "binary_data_output",
+];
+const INPUT_PARAMETERS: &[&str] = &[
+    "shared_memory_region",
+    "shared_memory_offset",
+    "shared_memory_byte_size",
+    "binary_data_size",
+];
+const OUTPUT_PARAMETERS: &[&str] = &[
+    "shared_memory_region",
+    "shared_memory_offset",
+    "shared_memory_byte_size",
+    "binary_data",
+    "classification",
+];
+
+/// How parameters that Triton does not define are handled. This version of
+/// the in-process API has no way to pass request parameters on to backends,
+/// so they can only be dropped or refused.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum UnknownParameters {
+    /// Drop them, the request is served as if they were not set.
+    #[default]
+    Ignore,
+    /// Fail the request with an INVALID_ARG error.
+    Reject,
+}
+
+impl std::str::FromStr for UnknownParameters {
+    type Err = RustisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "ignore" => Ok(UnknownParameters::Ignore),
+            "reject" => Ok(UnknownParameters::Reject),
+            _ => Err(RustisError::Validation(format!(
+                "unknown parameters policy must be 'ignore' or 'reject', got '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// How unary inference handles decoupled models, which may send any number
+/// of responses to a request.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DecoupledModels {
+    /// Fail the request with an UNSUPPORTED error, as Triton's frontends do.
+    #[default]
+    Reject,
+    /// Wait for every response and concatenate their outputs along the first
+    /// dimension.
+    Aggregate,
+}
+
+impl std::str::FromStr for DecoupledModels {
+    type Err = RustisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(DecoupledModels::Reject),
+            "aggregate" => Ok(DecoupledModels::Aggregate),
+            _ => Err(RustisError::Validation(format!(
+                "decoupled models policy must be 'reject' or 'aggregate', got '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// Merges the responses of a decoupled model into one: outputs of the same
+/// name are concatenated along their first dimension, in response order.
+/// Parameters of later responses win. `raw_output` is whether the request
+/// asked for raw contents, outputs in `shared_memory` are never concatenated.
+fn aggregate_responses(
+    responses: Vec<infer_proto::ModelInferResponse>,
+    raw_output: bool,
+    shared_memory: &HashSet<String>,
+) -> Result<Option<infer_proto::ModelInferResponse>> {
+    // half precision outputs switch their responses to raw contents
+    let raw_output = raw_output || responses.iter().any(|r| !r.raw_output_contents.is_empty());
+    let mut responses = responses.into_iter();
+    let first = match responses.next() {
+        Some(r) => r,
+        None => return Ok(None),
+    };
+    let mut aggregate = infer_proto::ModelInferResponse {
+        model_name: first.model_name.clone(),
+        model_version: first.model_version.clone(),
+        id: first.id.clone(),
+        ..Default::default()
+    };
+    for response in std::iter::once(first).chain(responses) {
+        // raw responses have an entry for each output, empty ones included
+        if !response.outputs.is_empty() && raw_output == response.raw_output_contents.is_empty() {
+            return Err(RustisError::Unsupported(
+                "cannot aggregate raw and typed output contents".to_string(),
+            ));
+        }
+        aggregate.parameters.extend(response.parameters);
+        let mut raw_contents = response.raw_output_contents.into_iter();
+        for output in response.outputs {
+            let raw_content = raw_contents.next();
+            let index = match aggregate.outputs.iter().position(|o| o.name == output.name) {
+                Some(index) => index,
+                None => {
+                    aggregate.outputs.push(output);
+                    aggregate.raw_output_contents.extend(raw_content);
+                    continue;
+                }
+            };
+            if shared_memory.contains(&output.name) {
+                // every response wrote to the same region, which holds the last one
+                aggregate.outputs[index] = output;
+                continue;
+            }
+            let merged = &mut aggregate.outputs[index];
+            if merged.datatype != output.datatype
+                || merged.shape.is_empty()
+                || merged.shape.get(1..) != output.shape.get(1..)
+            {
+                return Err(RustisError::Unsupported(format!(
+                    "cannot concatenate output '{}' of shape {:?} with shape {:?}",
+                    output.name, merged.shape, output.shape
+                )));
+            }
+            merged.shape[0] += output.shape[0];
+            if let Some(raw_content) = raw_content {
+                aggregate.raw_output_contents[index].extend(raw_content);
+            }
+            if let (Some(merged), Some(contents)) = (merged.contents.as_mut(), output.contents) {
+                merged.bool_contents.extend(contents.bool_contents);
+                merged.int_contents.extend(contents.int_contents);
+                merged.int64_contents.extend(contents.int64_contents);
+                merged.uint_contents.extend(contents.uint_contents);
+                merged.uint64_contents.extend(contents.uint64_contents);
+                merged.fp32_contents.extend(contents.fp32_contents);
+                merged.fp64_contents.extend(contents.fp64_contents);
+                merged.bytes_contents.extend(contents.bytes_contents);
+            }
+        }
+    }
+    Ok(Some(aggregate))
+}
+
+fn bool_parameter(
+    parameters: &HashMap<String, infer_proto::InferParameter>,
+    key: &str,
+) -> Result<Option<bool>> {
+    use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+    match parameters.get(key).and_then(|p| p.parameter_choice.as_ref()) {
+        Some(ParameterChoice::BoolParam(v)) => Ok(Some(*v)),
+        Some(_) => Err(RustisError::Validation(format!(
+            "invalid value for '{}' parameter, expected bool",
+            key
+        ))),
+        None => Ok(None),
+    }
+}
+
+fn uint_parameter(
+    parameters: &HashMap<String, infer_proto::InferParameter>,
+    key: &str,
+) -> Result<Option<u64>> {
+    use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+    match parameters.get(key).and_then(|p| p.parameter_choice.as_ref()) {
+        Some(ParameterChoice::Int64Param(v)) if *v >= 0 => Ok(Some(*v as u64)),
+        Some(_) => Err(RustisError::Validation(format!(
+            "invalid value for '{}' parameter, expected non-negative int64",
+            key
+        ))),
+        None => Ok(None),
+    }
+}
+
+/// Scheduling and output format parameters of a request.
+#[derive(Debug, Default, PartialEq)]
+struct RequestParameters {
+    priority: Option<u32>,
+    timeout: Option<u64>,
+    binary_data_output: Option<bool>,
+}
+
+impl RequestParameters {
+    fn parse(parameters: &HashMap<String, infer_proto::InferParameter>) -> Result<Self> {
+        let priority = match uint_parameter(parameters, "priority")? {
+            Some(v) => Some(u32::try_from(v).map_err(|_| {
+                RustisError::Validation(format!("priority {} is out of range", v))
+            })?),
+            None => None,
+        };
+        Ok(RequestParameters {
+            priority,
+            timeout: uint_parameter(parameters, "timeout")?,
+            binary_data_output: bool_parameter(parameters, "binary_data_output")?,
+        })
+    }
+}
+
+/// Format parameters of a requested output.
+#[derive(Debug, Default, PartialEq)]
+struct OutputParameters {
+    binary_data: Option<bool>,
+    classification: Option<u64>,
+}
+
+impl OutputParameters {
+    fn parse(parameters: &HashMap<String, infer_proto::InferParameter>) -> Result<Self> {
+        let classification = match uint_parameter(parameters, "classification")? {
+            Some(0) => {
+                return Err(RustisError::Validation(
+                    "invalid value for 'classification' parameter, expected positive int64"
+                        .to_string(),
+                ))
+            }
+            classification => classification,
+        };
+        Ok(OutputParameters {
+            binary_data: bool_parameter(parameters, "binary_data")?,
+            classification,
+        })
+    }
+}
+
+/// Returns whether the outputs of `pb_request` are sent in `raw_output_contents`.
+/// `binary_data_output` sets the default, which per-output `binary_data`
+/// overrides. Without either, outputs are raw when inputs are.
+fn raw_output(
+    pb_request: &infer_proto::ModelInferRequest,
+    parameters: &RequestParameters,
+) -> Result<bool> {
+    let default = parameters
+        .binary_data_output
+        .unwrap_or(!pb_request.raw_input_contents.is_empty());
+    let mut raw_output = None;
+    for output in &pb_request.outputs {
+        let binary_data = OutputParameters::parse(&output.parameters)?
+            .binary_data
+            .unwrap_or(default);
+        // a response has raw contents for all of its outputs or for none
+        if raw_output.is_some_and(|raw| raw != binary_data) {
+            return Err(RustisError::Validation(
+                "'binary_data' must be the same for all outputs".to_string(),
+            ));
+        }
+        raw_output = Some(binary_data);
+    }
+    Ok(raw_output.unwrap_or(default))
+}
+
+/// Sequence batcher controls given by the `sequence_id`, `sequence_start`
+/// and `sequence_end` request parameters.
+#[derive(Debug, PartialEq)]
+struct SequenceParameters {
+    id: Option<CorrelationId>,
+    flags: u32,
+}
+
+impl SequenceParameters {
+    fn parse(parameters: &HashMap<String, infer_proto::InferParameter>) -> Result<Self> {
+        use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+        let id = match parameters
+            .get("sequence_id")
+            .and_then(|p| p.parameter_choice.as_ref())
+        {
+            Some(ParameterChoice::Int64Param(v)) if *v >= 0 => Some(CorrelationId::Int(*v as u64)),
+            Some(ParameterChoice::StringParam(v)) if !v.contains('\0') => {
+                Some(CorrelationId::String(v.clone()))
+            }
+            Some(_) => {
+                return Err(RustisError::Validation(
+                    "invalid value for 'sequence_id' parameter, expected non-negative int64 or string"
+                        .to_string(),
+                ))
+            }
+            None => None,
+        };
+        let mut flags = 0;
+        if bool_parameter(parameters, "sequence_start")?.unwrap_or(false) {
+            flags |= tritonserver_requestflag_enum_TRITONSERVER_REQUEST_FLAG_SEQUENCE_START;
+        }
+        if bool_parameter(parameters, "sequence_end")?.unwrap_or(false) {
+            flags |= tritonserver_requestflag_enum_TRITONSERVER_REQUEST_FLAG_SEQUENCE_END;
+        }
+        Ok(SequenceParameters { id, flags })
+    }
+
+    fn apply(&self, request: &mut InferRequest) -> Result<(), TritonError> {
+        if let Some(id) = &self.id {
+            request.set_correlation(id)?;
+        }
+        if self.flags != 0 {
+            request.set_flags(self.flags)?;
+        }
+        Ok(())
+    }
+}
+
+/// Overrides applied when loading a model, without writing to the repository.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LoadOptions {
+    /// Model configuration in JSON, used instead of the `config.pbtxt` of the
+    /// repository.
+    pub config_json: Option<String>,
+    /// Content of the model files by path in the model directory, such as
+    /// `1/model.onnx`. Triton only accepts them together with `config_json`.
+    pub files: HashMap<String, Vec<u8>>,
+}
+
+impl LoadOptions {
+    fn validate(&self) -> Result<(), TritonError> {
+        let invalid_arg = |msg: String| {
+            TritonError::new(
+                TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG,
+                msg,
+            )
+        };
+        match &self.config_json {
+            Some(config) => {
+                if let Err(e) = serde_json::from_str::<serde_json::Value>(config) {
+                    return Err(invalid_arg(format!("invalid model config: {}", e)));
+                }
+            }
+            None if !self.files.is_empty() => {
+                return Err(invalid_arg(
+                    "model files can only be overridden together with the model config"
+                        .to_string(),
+                ))
+            }
+            None => {}
+        }
+        for path in self.files.keys() {
+            let relative = std::path::Path::new(path)
+                .components()
+                .all(|c| matches!(c, std::path::Component::Normal(_)));
+            if path.is_empty() || !relative {
+                return Err(invalid_arg(format!(
+                    "model file '{}' must be a relative path inside the model directory",
+                    path
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    // the `config` and `file:<path>` load parameters
+    fn into_parameters(self) -> Result<Vec<Parameter>, TritonError> {
+        self.validate()?;
+        let mut parameters = Vec::with_capacity(self.files.len() + 1);
+        if let Some(config) = &self.config_json {
+            parameters.push(Parameter::from_string("config", config)?);
+        }
+        for (path, content) in self.files {
+            parameters.push(Parameter::from_bytes(&format!("file:{}", path), content)?);
+        }
+        Ok(parameters)
+    }
 }
 
 impl Server {
@@ -45,9 +479,71 @@ impl Server {
         Ok(Server {
             _server: server,
             response_allocator,
+            shared_memory: SharedMemoryManager::default(),
+            conversion_observer: None,
+            unknown_parameters: UnknownParameters::default(),
+            decoupled_models: DecoupledModels::default(),
+            transaction_policies: Mutex::new(HashMap::new()),
+            tracer: None,
         })
     }
 
+    /// Registers `observer` to be called with the duration of every
+    /// protobuf request and response conversion.
+    pub fn set_conversion_observer(&mut self, observer: ConversionObserver) {
+        self.conversion_observer = Some(observer);
+    }
+
+    pub fn set_unknown_parameters(&mut self, policy: UnknownParameters) {
+        self.unknown_parameters = policy;
+    }
+
+    pub fn set_decoupled_models(&mut self, policy: DecoupledModels) {
+        self.decoupled_models = policy;
+    }
+
+    /// Traces the protobuf requests `tracer` samples. Only requests of
+    /// `infer` and `infer_stream` are traced, those of `infer_tensors` and
+    /// `infer_async` never are.
+    pub fn set_tracer(&mut self, tracer: Tracer) {
+        self.tracer = Some(tracer);
+    }
+
+    /// Applies the unknown parameters policy to the parameters of a request,
+    /// or of its tensor `tensor`, that are not in `known`.
+    fn check_parameters(
+        &self,
+        parameters: &HashMap<String, infer_proto::InferParameter>,
+        known: &[&str],
+        tensor: Option<&str>,
+    ) -> Result<()> {
+        if self.unknown_parameters == UnknownParameters::Ignore {
+            return Ok(());
+        }
+        match parameters.keys().find(|k| !known.contains(&k.as_str())) {
+            Some(name) => Err(RustisError::Validation(match tensor {
+                Some(tensor) => format!("unknown parameter '{}' for tensor '{}'", name, tensor),
+                None => format!("unknown request parameter '{}'", name),
+            })),
+            None => Ok(()),
+        }
+    }
+
+    fn observe_conversion(&self, conversion: Conversion, start: Instant) {
+        if let Some(observer) = &self.conversion_observer {
+            observer(conversion, start.elapsed());
+        }
+    }
+
+    pub fn metrics(&self) -> Result<TritonMetrics, TritonError> {
+        let mut metrics: *mut TRITONSERVER_Metrics = null_mut();
+        let err = unsafe { TRITONSERVER_ServerMetrics(self._server, &mut metrics) };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(TritonMetrics::from_ptr(metrics))
+    }
+
     pub fn is_live(&self) -> Result<bool, TritonError> {
         let mut live = false;
         let err = unsafe { TRITONSERVER_ServerIsLive(self._server, &mut live as *mut bool) };
@@ -67,20 +563,220 @@ impl Server {
     }
 
     pub fn load_model(&self, model_name: &str) -> Result<(), TritonError> {
-        let c_model_name = std::ffi::CString::new(model_name).unwrap();
+        let c_model_name = c_string("model name", model_name)?;
         let err = unsafe { TRITONSERVER_ServerLoadModel(self._server, c_model_name.as_ptr()) };
+        self.forget_transaction_policies();
         if !err.is_null() {
             return Err(TritonError { _err: err });
         }
         Ok(())
     }
 
+    pub fn load_model_with_parameters(
+        &self,
+        model_name: &str,
+        parameters: &[Parameter],
+    ) -> Result<(), TritonError> {
+        let c_model_name = c_string("model name", model_name)?;
+        let mut raw_parameters = parameters
+            .iter()
+            .map(|p| p.raw_pointer() as *const TRITONSERVER_Parameter)
+            .collect::<Vec<_>>();
+        let err = unsafe {
+            TRITONSERVER_ServerLoadModelWithParameters(
+                self._server,
+                c_model_name.as_ptr(),
+                raw_parameters.as_mut_ptr(),
+                raw_parameters.len() as u64,
+            )
+        };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    /// Loads `model_name` with the config and files of `options` in place of
+    /// those of the repository.
+    pub fn load_model_with(
+        &self,
+        model_name: &str,
+        options: LoadOptions,
+    ) -> Result<(), TritonError> {
+        let parameters = options.into_parameters()?;
+        self.load_model_with_parameters(model_name, &parameters)
+    }
+
+    pub fn unload_model(&self, model_name: &str) -> Result<(), TritonError> {
+        let c_model_name = c_string("model name", model_name)?;
+        let err = unsafe { TRITONSERVER_ServerUnloadModel(self._server, c_model_name.as_ptr()) };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    pub fn unload_model_and_dependents(&self, model_name: &str) -> Result<(), TritonError> {
+        let c_model_name = c_string("model name", model_name)?;
+        let err = unsafe {
+            TRITONSERVER_ServerUnloadModelAndDependents(self._server, c_model_name.as_ptr())
+        };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    /// Returns the models of all repositories, with `ready_only` set only
+    /// those that are ready for inferencing.
+    pub fn model_index(&self, ready_only: bool) -> Result<Vec<ModelIndexEntry>> {
+        let mut model_index: *mut TRITONSERVER_Message = null_mut();
+        let flags = if ready_only {
+            tritonserver_modelindexflag_enum_TRITONSERVER_INDEX_FLAG_READY
+        } else {
+            0
+        };
+        let err = unsafe { TRITONSERVER_ServerModelIndex(self._server, flags, &mut model_index) };
+        if !err.is_null() {
+            return Err(RustisError::from(TritonError::from_ptr(err)));
+        }
+        let json_message = TritonMessage::from_ptr(model_index).to_serialized_json()?;
+        Ok(model_index::parse_model_index(&json_message)?)
+    }
+
+    /// Adds the repository at `path` while the server runs. `name_mapping`
+    /// serves the models of the given directories under other names, e.g. to
+    /// avoid clashes with the models of other repositories.
+    pub fn register_model_repository(
+        &self,
+        path: &str,
+        name_mapping: &HashMap<String, String>,
+    ) -> Result<(), TritonError> {
+        let c_path = c_string("repository path", path)?;
+        let mapping = name_mapping
+            .iter()
+            .map(|(directory, name)| Parameter::from_string(directory, name))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut raw_mapping = mapping
+            .iter()
+            .map(|p| p.raw_pointer() as *const TRITONSERVER_Parameter)
+            .collect::<Vec<_>>();
+        let err = unsafe {
+            TRITONSERVER_ServerRegisterModelRepository(
+                self._server,
+                c_path.as_ptr(),
+                raw_mapping.as_mut_ptr(),
+                raw_mapping.len() as u32,
+            )
+        };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    /// Removes the repository registered at `path`, its models stay loaded
+    /// until they are unloaded.
+    pub fn unregister_model_repository(&self, path: &str) -> Result<(), TritonError> {
+        let c_path = c_string("repository path", path)?;
+        let err =
+            unsafe { TRITONSERVER_ServerUnregisterModelRepository(self._server, c_path.as_ptr()) };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    /// Loads, reloads and unloads models to match the repositories, only
+    /// allowed in the poll model control mode.
+    pub fn poll_model_repository(&self) -> Result<(), TritonError> {
+        let err = unsafe { TRITONSERVER_ServerPollModelRepository(self._server) };
+        self.forget_transaction_policies();
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(())
+    }
+
+    /// Returns how version `model_version` of `model_name` batches, -1 lets
+    /// the server choose the version based on the model's policy.
+    pub fn model_batch_properties(
+        &self,
+        model_name: &str,
+        model_version: i64,
+    ) -> Result<BatchProperties> {
+        let c_model_name = c_string("model name", model_name)?;
+        let mut flags = 0u32;
+        let err = unsafe {
+            TRITONSERVER_ServerModelBatchProperties(
+                self._server,
+                c_model_name.as_ptr(),
+                model_version,
+                &mut flags,
+                null_mut(),
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err).into());
+        }
+        BatchProperties::try_from(flags)
+    }
+
+    /// Returns whether version `model_version` of `model_name` is decoupled,
+    /// -1 lets the server choose the version based on the model's policy.
+    pub fn model_transaction_policy(
+        &self,
+        model_name: &str,
+        model_version: i64,
+    ) -> Result<TransactionPolicy> {
+        let c_model_name = c_string("model name", model_name)?;
+        let mut flags = 0u32;
+        let err = unsafe {
+            TRITONSERVER_ServerModelTransactionProperties(
+                self._server,
+                c_model_name.as_ptr(),
+                model_version,
+                &mut flags,
+                null_mut(),
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err).into());
+        }
+        TransactionPolicy::try_from(flags)
+    }
+
+    /// Like `model_transaction_policy`, but only asks the server once per
+    /// model name and version until models are loaded or unloaded.
+    fn cached_transaction_policy(
+        &self,
+        model_name: &str,
+        model_version: i64,
+    ) -> Result<TransactionPolicy> {
+        let key = (model_name.to_string(), model_version);
+        if let Some(policy) = self.transaction_policies.lock().unwrap().get(&key) {
+            return Ok(*policy);
+        }
+        let policy = self.model_transaction_policy(model_name, model_version)?;
+        self.transaction_policies.lock().unwrap().insert(key, policy);
+        Ok(policy)
+    }
+
+    fn forget_transaction_policies(&self) {
+        self.transaction_policies.lock().unwrap().clear();
+    }
+
     pub fn is_model_ready(
         &self,
         model_name: &str,
         model_version: i64,
     ) -> Result<bool, TritonError> {
-        let c_model_name = CString::new(model_name).unwrap();
+        let c_model_name = c_string("model name", model_name)?;
         let mut ready = false;
         let err = unsafe {
             TRITONSERVER_ServerModelIsReady(
@@ -112,7 +808,7 @@ impl Server {
         model_version: i64,
     ) -> Result<String, TritonError> {
         let mut model_metadata: *mut TRITONSERVER_Message = null_mut();
-        let c_model_name = CString::new(model_name).unwrap();
+        let c_model_name = c_string("model name", model_name)?;
         let err = unsafe {
             TRITONSERVER_ServerModelMetadata(
                 self._server,
@@ -127,15 +823,103 @@ impl Server {
         let json_message = TritonMessage::from_ptr(model_metadata).to_serialized_json()?;
         Ok(json_message)
     }
-    // TODO: add InferenceTrace
-    pub fn infer_async(&self, request: &mut InferRequest) -> Result<(), TritonError> {
+
+    /// Returns the statistics of `model_name`, or of every available model when
+    /// the name is empty. A `model_version` of -1 lets the server choose the
+    /// version based on the model's policy.
+    pub fn model_statistics(
+        &self,
+        model_name: &str,
+        model_version: i64,
+    ) -> Result<Vec<ModelStatistics>> {
+        let mut model_stats: *mut TRITONSERVER_Message = null_mut();
+        let c_model_name = c_string("model name", model_name)?;
+        let err = unsafe {
+            TRITONSERVER_ServerModelStatistics(
+                self._server,
+                c_model_name.as_ptr(),
+                model_version,
+                &mut model_stats,
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err).into());
+        }
+        let json_message = TritonMessage::from_ptr(model_stats).to_serialized_json()?;
+        Ok(statistics::parse_model_statistics(&json_message)?)
+    }
+
+    /// Returns the effective configuration of a model. `config_version` is the
+    /// model configuration schema version, currently only 1 is supported.
+    pub fn model_config(
+        &self,
+        model_name: &str,
+        model_version: i64,
+        config_version: u32,
+    ) -> Result<ModelConfig> {
+        let mut model_config: *mut TRITONSERVER_Message = null_mut();
+        let c_model_name = c_string("model name", model_name)?;
+        let err = unsafe {
+            TRITONSERVER_ServerModelConfig(
+                self._server,
+                c_model_name.as_ptr(),
+                model_version,
+                config_version,
+                &mut model_config,
+            )
+        };
+        if !err.is_null() {
+            return Err(TritonError::from_ptr(err).into());
+        }
+        let json_message = TritonMessage::from_ptr(model_config).to_serialized_json()?;
+        Ok(ModelConfig::from_json(&json_message)?)
+    }
+
+    /// Submits `request`, together with its trace if it has one. Both are
+    /// deleted if Triton refuses it.
+    pub fn infer_async(&self, request: InferRequest) -> Result<SubmittedRequest, TritonError> {
         let err = unsafe {
-            TRITONSERVER_ServerInferAsync(self._server, request.raw_pointer(), ptr::null_mut())
+            TRITONSERVER_ServerInferAsync(
+                self._server,
+                request.raw_pointer(),
+                request.trace_pointer(),
+            )
         };
         if !err.is_null() {
             return Err(TritonError { _err: err });
         }
-        Ok(())
+        Ok(request.into_submitted())
+    }
+
+    /// Runs version `model_version` of `model_name` on `inputs` and returns
+    /// all of its outputs, without going through protobuf.
+    pub async fn infer_tensors(
+        &self,
+        model_name: &str,
+        model_version: i64,
+        inputs: HashMap<String, Tensor>,
+    ) -> Result<HashMap<String, Tensor>> {
+        let mut builder = InferRequestBuilder::new(model_name).version(model_version);
+        for (name, tensor) in &inputs {
+            builder = builder.input(name, tensor.datatype(), &tensor.shape(), tensor.to_bytes());
+        }
+        let request = builder.build(self).map_err(RustisError::from)?;
+        let submitted = self.infer_async(request).map_err(RustisError::from)?;
+        let response = match submitted.receiver {
+            Some(mut receiver) => receiver.recv().await,
+            None => None,
+        };
+        let response = response.ok_or(RustisError::ChannelClosed)?;
+        if let Some(e) = response.error() {
+            return Err(RustisError::from(e));
+        }
+        let output_count = response.output_count().map_err(RustisError::from)?;
+        let mut outputs = HashMap::with_capacity(output_count as usize);
+        for index in 0..output_count {
+            let output = response.output(index).map_err(RustisError::from)?;
+            outputs.insert(output.name().to_owned(), Tensor::from_output(&output)?);
+        }
+        Ok(outputs)
     }
 
     pub fn parse_pb_request(
@@ -143,27 +927,30 @@ impl Server {
         pb_request: Box<infer_proto::ModelInferRequest>,
     ) -> Result<InferRequest> {
         let raw_input_length = pb_request.raw_input_contents.len();
-        let input_length = pb_request.inputs.len();
+        // inputs in shared memory have no entry in raw_input_contents
+        let input_length = pb_request
+            .inputs
+            .iter()
+            .filter(|i| !i.parameters.contains_key("shared_memory_region"))
+            .count();
         if raw_input_length != 0 && raw_input_length != input_length {
-            return Err(InferError::new(
+            return Err(RustisError::Validation(
                 "raw_input_contents and inputs must be of same length".to_string(),
-            )
-            .into());
+            ));
         }
+        let parameters = RequestParameters::parse(&pb_request.parameters)?;
+        let raw_output = raw_output(&pb_request, &parameters)?;
         let model_version = pb_request.model_version.parse::<i64>().unwrap_or(-1);
         let mut infer_request = match InferRequest::new(
             self,
             &pb_request.model_name,
             model_version,
-            raw_input_length > 0,
+            raw_output,
         ) {
             Ok(r) => r,
-            Err(e) => return Err(InferError::new(e.msg()).into()),
-        };
-        if let Err(e) = self.copy_request_data(&mut infer_request, pb_request) {
-            infer_request.delete();
-            return Err(e);
+            Err(e) => return Err(RustisError::from(e)),
         };
+        self.copy_request_data(&mut infer_request, pb_request, &parameters)?;
         Ok(infer_request)
     }
 
@@ -172,29 +959,69 @@ impl Server {
         &self,
         infer_request: &mut InferRequest,
         pb_request: Box<infer_proto::ModelInferRequest>,
+        parameters: &RequestParameters,
     ) -> Result<()> {
         match infer_request.set_id(&pb_request.id) {
             Ok(_) => (),
-            Err(e) => return Err(InferError::new(e.msg()).into()),
+            Err(e) => return Err(RustisError::from(e)),
         };
+        self.check_parameters(&pb_request.parameters, REQUEST_PARAMETERS, None)?;
+        SequenceParameters::parse(&pb_request.parameters)?
+            .apply(infer_request)
+            .map_err(RustisError::from)?;
+        if let Some(priority) = parameters.priority {
+            infer_request
+                .set_priority(priority)
+                .map_err(RustisError::from)?;
+        }
+        if let Some(timeout) = parameters.timeout {
+            infer_request
+                .set_timeout_micro_seconds(timeout)
+                .map_err(RustisError::from)?;
+        }
         let raw_input_length = pb_request.raw_input_contents.len();
         let mut bytes_slice = vec![];
-        for (i, input_tensor) in pb_request.inputs.iter().enumerate() {
+        let mut regions = vec![];
+        let mut raw_index = 0;
+        for input_tensor in pb_request.inputs.iter() {
+            self.check_parameters(
+                &input_tensor.parameters,
+                INPUT_PARAMETERS,
+                Some(&input_tensor.name),
+            )?;
             let data_type = utils::string_to_data_type(&input_tensor.datatype);
             if data_type == TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INVALID {
-                return Err(InferError::new("Invalid datatype".to_owned()).into());
+                return Err(RustisError::Validation("Invalid datatype".to_owned()));
             }
             // check shape
             for s in &input_tensor.shape {
                 if *s < 0 {
-                    return Err(InferError::new("Invalid shape".to_string()).into());
+                    return Err(RustisError::Validation("Invalid shape".to_string()));
                 }
             }
             match infer_request.add_input(&input_tensor.name, data_type, &input_tensor.shape) {
                 Ok(_) => (),
-                Err(e) => return Err(InferError::new(e.msg()).into()),
+                Err(e) => return Err(RustisError::from(e)),
             };
-            if raw_input_length != 0 {
+            if let Some(shm) = SharedMemoryParameters::parse(&input_tensor.parameters)? {
+                let region = self.shared_memory.get(&shm.region)?;
+                let byte_size = shm.byte_size(&region);
+                let base = region.slice(shm.offset, byte_size)?;
+                unsafe {
+                    infer_request
+                        .append_input_data(
+                            &input_tensor.name,
+                            base as *const c_void,
+                            byte_size,
+                            TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
+                            0,
+                        )
+                        .map_err(RustisError::from)?;
+                }
+                regions.push(region);
+            } else if raw_input_length != 0 {
+                let i = raw_index;
+                raw_index += 1;
                 let length = pb_request.raw_input_contents[i].len();
                     infer_request
                         .append_input_data_generic1(
@@ -204,8 +1031,14 @@ impl Server {
                             TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                             0,
                         )
-                        .map_err(|e| InferError::new(e.msg()))?;
+                        .map_err(RustisError::from)?;
             } else {
+                if input_tensor.contents.is_none() {
+                    return Err(RustisError::Validation(format!(
+                        "input '{}' has no data in contents or raw_input_contents",
+                        input_tensor.name
+                    )));
+                }
                 match data_type {
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BOOL => {
                         if let Some(contents) = &input_tensor.contents {
@@ -219,7 +1052,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                             
                         }
                     }
@@ -240,7 +1073,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT16 => {
@@ -260,7 +1093,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT32 => {
@@ -273,7 +1106,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT64 => {
@@ -286,7 +1119,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT8 => {
@@ -306,7 +1139,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                             }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT16 => {
@@ -325,7 +1158,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32 => {
@@ -338,7 +1171,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT64 => {
@@ -351,7 +1184,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32 => {
@@ -364,7 +1197,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP64 => {
@@ -377,7 +1210,7 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                         }
                     }
                     TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BYTES => {
@@ -402,33 +1235,74 @@ impl Server {
                                         TRITONSERVER_memorytype_enum_TRITONSERVER_MEMORY_CPU,
                                         0,
                                     )
-                                    .map_err(|e| InferError::new(e.msg()))?;
+                                    .map_err(RustisError::from)?;
                             bytes_slice.push(data);
                         }
                     }
-
-                    _ => (),
+                    // InferTensorContents has no field for half precision values
+                    TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP16
+                    | TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BF16 => {
+                        return Err(RustisError::Validation(format!(
+                            "input '{}' of type {} must be sent in raw_input_contents",
+                            input_tensor.name, input_tensor.datatype
+                        )));
+                    }
+                    _ => {
+                        return Err(RustisError::Validation(format!(
+                            "unsupported datatype {} for input '{}'",
+                            input_tensor.datatype, input_tensor.name
+                        )));
+                    }
                 }
             }
         }
+        let mut output_buffers = OutputBuffers::default();
         for output_tensor in pb_request.outputs.iter() {
+            self.check_parameters(
+                &output_tensor.parameters,
+                OUTPUT_PARAMETERS,
+                Some(&output_tensor.name),
+            )?;
+            let classification = OutputParameters::parse(&output_tensor.parameters)?.classification;
             infer_request
                 .add_requested_output(&output_tensor.name)
-                .map_err(|e| InferError::new(e.msg()))?;
+                .map_err(RustisError::from)?;
+            if let Some(shm) = SharedMemoryParameters::parse(&output_tensor.parameters)? {
+                if classification.is_some() {
+                    return Err(RustisError::Validation(format!(
+                        "output '{}' cannot be both a classification and in shared memory",
+                        output_tensor.name
+                    )));
+                }
+                let region = self.shared_memory.get(&shm.region)?;
+                let byte_size = shm.byte_size(&region);
+                region.slice(shm.offset, byte_size)?;
+                output_buffers.insert(
+                    &output_tensor.name,
+                    OutputBuffer {
+                        region,
+                        offset: shm.offset,
+                        byte_size,
+                    },
+                );
+            }
+            if let Some(count) = classification {
+                infer_request
+                    .classification
+                    .insert(output_tensor.name.clone(), count as usize);
+            }
         }
 
-        let request_data_holder = Box::new(RequestDataHolder::new(pb_request, bytes_slice));
+        let request_data_holder =
+            Box::new(RequestDataHolder::new(pb_request, bytes_slice, regions));
 
         infer_request
             .set_release_callback(request_data_holder)
-            .map_err(|e| InferError::new(e.msg()))?;
+            .map_err(RustisError::from)?;
 
-        let (sender, receiver) = mpsc::channel(1);
         infer_request
-            .set_response_callback(&self.response_allocator, sender)
-            .map_err(|e| InferError::new(e.msg()))?;
-
-        infer_request.set_response_receiver(receiver);
+            .set_response_callback(&self.response_allocator, output_buffers)
+            .map_err(RustisError::from)?;
 
         Ok(())
     }
@@ -438,20 +1312,21 @@ impl Server {
         &self,
         response: InferResponse,
         raw_output: bool,
+        classification: &HashMap<String, usize>,
     ) -> Result<infer_proto::ModelInferResponse> {
         let (model, version) = match response.model() {
             Ok((m, v)) => (m, v),
-            Err(e) => return Err(InferError::new(e.msg()).into()),
+            Err(e) => return Err(RustisError::from(e)),
         };
 
         let request_id = match response.id() {
             Ok(id) => id,
-            Err(e) => return Err(InferError::new(e.msg()).into()),
+            Err(e) => return Err(RustisError::from(e)),
         };
 
         let output_count = match response.output_count() {
             Ok(c) => c,
-            Err(e) => return Err(InferError::new(e.msg()).into()),
+            Err(e) => return Err(RustisError::from(e)),
         };
 
         let mut pb_response = infer_proto::ModelInferResponse {
@@ -463,11 +1338,38 @@ impl Server {
             raw_output_contents: Vec::with_capacity(output_count as usize),
         };
 
-        for i in 0..output_count {
-            let output_info = match response.output(i) {
-                Ok(o) => o,
-                Err(e) => return Err(InferError::new(e.msg()).into()),
+        for (name, value) in response.parameters()? {
+            use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+            let choice = match value {
+                ParameterValue::Bool(v) => ParameterChoice::BoolParam(v),
+                ParameterValue::Int(v) => ParameterChoice::Int64Param(v),
+                ParameterValue::String(v) => ParameterChoice::StringParam(v),
             };
+            pb_response.parameters.insert(
+                name,
+                infer_proto::InferParameter {
+                    parameter_choice: Some(choice),
+                },
+            );
+        }
+
+        let outputs = (0..output_count)
+            .map(|i| response.output(i))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(RustisError::from)?;
+        // half precision values have no typed protobuf field, and a response
+        // carries raw contents for all of its outputs or for none
+        let raw_output = raw_output
+            || outputs.iter().any(|o| {
+                matches!(
+                    o.datatype(),
+                    TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP16
+                        | TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BF16
+                ) && !classification.contains_key(o.name())
+            });
+
+        for (i, output_info) in outputs.into_iter().enumerate() {
+            let i = i as u32;
 
             let mut out_tensor = infer_proto::model_infer_response::InferOutputTensor {
                 name: output_info.name().to_owned(),
@@ -476,7 +1378,26 @@ impl Server {
                 ..Default::default()
             };
 
-            if raw_output {
+            if let Some(&count) = classification.get(output_info.name()) {
+                let result = Classification::top_k(&response, i, &output_info, count)?;
+                out_tensor.datatype = "BYTES".to_owned();
+                out_tensor.shape = result.shape;
+                if raw_output {
+                    pb_response
+                        .raw_output_contents
+                        .push(classification::serialize_bytes(&result.classes));
+                } else {
+                    out_tensor.contents = Some(infer_proto::InferTensorContents {
+                        bytes_contents: result.classes,
+                        ..Default::default()
+                    });
+                }
+            } else if !output_info.user_pointer().is_null() {
+                // written to shared memory by the allocator, nothing to copy
+                if raw_output {
+                    pb_response.raw_output_contents.push(vec![]);
+                }
+            } else if raw_output {
                 unsafe {
                     let c_data = Vec::from_raw_parts(
                         output_info.base() as *mut u8,
@@ -663,21 +1584,33 @@ impl Server {
                         let data = c_data.clone();
                         let _ = c_data.into_raw_parts();
 
+                        // `len` elements, each a 4 byte little endian length and its bytes
+                        let malformed =
+                            || RustisError::Conversion("malformed BYTES output".to_string());
                         let mut bytes_contents = Vec::<Vec<u8>>::with_capacity(len);
                         let mut k: usize = 0;
-                            while k < len  {
-                                let len = u32::from_le_bytes(data[k..k+4].try_into()?);
-                                k += 4;
-                                bytes_contents.push(data[k..k+len as usize].into());
-                                k += len as usize;
-                            }
+                        for _ in 0..len {
+                            let element_len = data.get(k..k + 4).ok_or_else(malformed)?;
+                            let element_len = u32::from_le_bytes(
+                                element_len.try_into().map_err(|_| malformed())?,
+                            ) as usize;
+                            k += 4;
+                            let element = data.get(k..k + element_len).ok_or_else(malformed)?;
+                            bytes_contents.push(element.to_vec());
+                            k += element_len;
+                        }
                         out_tensor.contents = Some(infer_proto::InferTensorContents {
                             bytes_contents,
                             ..Default::default()
                         });
                     }
-
-                    _ => (),
+                    // half precision outputs always switch the response to raw contents
+                    _ => {
+                        return Err(RustisError::Internal(format!(
+                            "unsupported datatype {} for output '{}'",
+                            out_tensor.datatype, out_tensor.name
+                        )));
+                    }
                 }
             }
 
@@ -686,31 +1619,142 @@ impl Server {
         Ok(pb_response)
     }
 
+    fn submit(
+        &self,
+        request: Box<infer_proto::ModelInferRequest>,
+        timeout: u64,
+        trace_context: Option<TraceContext>,
+    ) -> Result<SubmittedRequest> {
+        let start = Instant::now();
+        let mut request = self.parse_pb_request(request)?;
+        self.observe_conversion(Conversion::Request, start);
+        if let Some(tracer) = &self.tracer {
+            if let Some(trace) = tracer.trace(trace_context.as_ref())? {
+                request.set_trace(trace);
+            }
+        }
+        // the tightest of `timeout` and the `timeout` parameter applies, zero is no timeout
+        let requested = request.timeout_micro_seconds()?;
+        if requested == 0 || (timeout != 0 && timeout < requested) {
+            request.set_timeout_micro_seconds(timeout)?;
+        }
+        match self.infer_async(request) {
+            Ok(request) => Ok(request),
+            Err(e) => Err(RustisError::from(e)),
+        }
+    }
+
+    /// Runs `request`, which is traced as part of the caller's trace when
+    /// there is a `trace_context` and the tracer samples it. Decoupled models
+    /// are rejected or have their responses aggregated, depending on the
+    /// decoupled models policy.
     pub async fn infer(
         &self,
         request: Box<infer_proto::ModelInferRequest>,
         timeout: u64,
+        trace_context: Option<TraceContext>,
     ) -> Result<infer_proto::ModelInferResponse> {
-        let mut request = match self.parse_pb_request(request) {
-            Ok(r) => r,
-            Err(e) => return Err(e),
+        let model_version = request.model_version.parse::<i64>().unwrap_or(-1);
+        let policy = self.cached_transaction_policy(&request.model_name, model_version)?;
+        if policy == TransactionPolicy::Decoupled
+            && self.decoupled_models == DecoupledModels::Reject
+        {
+            return Err(RustisError::Unsupported(format!(
+                "model '{}' is decoupled, use the streaming API to infer with it",
+                request.model_name
+            )));
+        }
+        let empty_response = infer_proto::ModelInferResponse {
+            model_name: request.model_name.clone(),
+            model_version: request.model_version.clone(),
+            id: request.id.clone(),
+            ..Default::default()
         };
-        request.set_timeout_micro_seconds(timeout)?;
-        if let Err(e) = self.infer_async(&mut request) {
-            request.delete();
-            return Err(InferError::new(e.msg()).into());
+        let shared_memory: HashSet<String> = request
+            .outputs
+            .iter()
+            .filter(|o| o.parameters.contains_key("shared_memory_region"))
+            .map(|o| o.name.clone())
+            .collect();
+        let request = self.submit(request, timeout, trace_context)?;
+        let mut receiver = match request.receiver {
+            Some(r) => r,
+            None => return Err(RustisError::ChannelClosed),
         };
 
-        match request.receiver.unwrap().recv().await {
-            Some(r) => {
-                if let Some(e) = r.error() {
-                    return Err(InferError::new(e.msg()).into());
+        if policy == TransactionPolicy::OneToOne {
+            return match receiver.recv().await {
+                Some(r) => {
+                    if let Some(e) = r.error() {
+                        return Err(RustisError::from(e));
+                    }
+                    let start = Instant::now();
+                    let pb_response =
+                        self.create_pb_response(r, request.raw_output, &request.classification);
+                    self.observe_conversion(Conversion::Response, start);
+                    pb_response
                 }
-                // TODO: implement fine control on whether to use raw output
-                self.create_pb_response(r, request.raw_output)
+                None => Err(RustisError::ChannelClosed),
+            };
+        }
+        // the channel closes after the final response
+        let mut pb_responses = vec![];
+        while let Some(r) = receiver.recv().await {
+            if let Some(e) = r.error() {
+                return Err(RustisError::from(e));
             }
-            None => Err(InferError::new("receive error".to_string()).into()),
+            let start = Instant::now();
+            pb_responses.push(self.create_pb_response(
+                r,
+                request.raw_output,
+                &request.classification,
+            )?);
+            self.observe_conversion(Conversion::Response, start);
         }
+        // a decoupled model may not respond at all
+        let aggregate = aggregate_responses(pb_responses, request.raw_output, &shared_memory)?;
+        Ok(aggregate.unwrap_or(empty_response))
+    }
+
+    /// Submits `request` and forwards every response it produces to `sender`,
+    /// converted with `map`, until Triton marks the request as complete.
+    /// Errors carried by individual responses are forwarded instead of
+    /// ending the stream.
+    pub async fn infer_stream<T, F>(
+        &self,
+        request: Box<infer_proto::ModelInferRequest>,
+        timeout: u64,
+        trace_context: Option<TraceContext>,
+        sender: &mpsc::Sender<T>,
+        map: F,
+    ) -> Result<()>
+    where
+        F: Fn(Result<infer_proto::ModelInferResponse>) -> T,
+    {
+        let request = self.submit(request, timeout, trace_context)?;
+        let raw_output = request.raw_output;
+        let classification = request.classification;
+        let mut receiver = match request.receiver {
+            Some(r) => r,
+            None => return Err(RustisError::ChannelClosed),
+        };
+
+        while let Some(r) = receiver.recv().await {
+            let pb_response = match r.error() {
+                Some(e) => Err(RustisError::from(e)),
+                None => {
+                    let start = Instant::now();
+                    let pb_response = self.create_pb_response(r, raw_output, &classification);
+                    self.observe_conversion(Conversion::Response, start);
+                    pb_response
+                }
+            };
+            if sender.send(map(pb_response)).await.is_err() {
+                // the consumer is gone, remaining responses are dropped by the callback
+                break;
+            }
+        }
+        Ok(())
     }
 }
 
@@ -723,62 +1767,47 @@ impl Drop for Server {
     }
 }
 
-impl Default for Server {
-    fn default() -> Self {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-        options.set_model_repository_path("../../../models").unwrap();
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                server
-            }
-            Err(e) => panic!("{:?}", e.msg()),
-        }
-    }
-}
 unsafe impl Send for Server {}
 unsafe impl Sync for Server {}
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::wrapper::model_index::ModelState;
+    use log::info;
 
-    #[test]
-    fn test() {
+    /// Starts a server on the test model repository in explicit model control
+    /// mode. The tests using it need Triton, run them with `--ignored`.
+    fn test_server() -> Server {
         let options = ServerOptions::new().unwrap();
-        // let  mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-
-        options.set_model_repository_path("../../../models").unwrap();
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-            }
-            Err(e) => error!("{:?}", e.msg()),
+        options
+            .set_model_repository_path("../../../models")
+            .unwrap();
+        let model_control_mode =
+            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
+        options.set_model_control_mode(model_control_mode).unwrap();
+        match Server::new(options) {
+            Ok(server) => server,
+            Err(e) => panic!("failed to start Triton: {}", e.msg()),
         }
     }
 
+    #[test]
+    #[ignore = "needs Triton and the test model repository"]
+    fn test() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+    }
+
+    use crate::wrapper::metrics::{MetricFamily, MetricKind};
     use ::infer_proto::infer_proto::InferTensorContents;
     use std::collections::HashMap;
     use std::fs;
 
-
     #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
     async fn test_addsub() {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
-
         // create request
 
         let request = infer_proto::ModelInferRequest {
@@ -821,324 +1850,766 @@ mod tests {
             raw_input_contents: vec![],
         };
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("addsub") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("addsub").unwrap();
 
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => {
-                            assert_eq!(content.fp32_contents, vec![2.0, 4.0, 6.0, 8.0])
-                        }
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => {
+                    assert_eq!(content.fp32_contents, vec![2.0, 4.0, 6.0, 8.0])
                 }
-            }
-            Err(e) => error!("{:?}", e.msg()),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
         }
     }
 
-    #[tokio::test]
-    async fn test_uint8() {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("modes");
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
+    #[test]
+    #[ignore = "needs Triton and the test model repository"]
+    fn test_repository() {
+        let server = test_server();
+        let config = r#"{"backend": "python", "max_batch_size": 0}"#;
+        let parameters = [Parameter::from_string("config", config).unwrap()];
+        server
+            .load_model_with_parameters("addsub", &parameters)
+            .unwrap();
+        let index = server.model_index(true).unwrap();
+        assert!(index.iter().any(|e| e.name == "addsub"));
+        assert_eq!(
+            server.model_transaction_policy("addsub", -1).unwrap(),
+            TransactionPolicy::OneToOne
+        );
+        assert!(matches!(
+            server.model_transaction_policy("add\0sub", -1),
+            Err(e) if e.code() == TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG
+        ));
+        // batching is off with a max_batch_size of 0
+        assert_eq!(
+            server.model_batch_properties("addsub", 1).unwrap(),
+            BatchProperties::Unknown
+        );
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("uint8") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
-                let request = infer_proto::ModelInferRequest {
+        server.unload_model("addsub").unwrap();
+        let index = server.model_index(false).unwrap();
+        let addsub = index.iter().find(|e| e.name == "addsub").unwrap();
+        assert_eq!(addsub.state, ModelState::Unavailable);
+
+        // the model file is pushed from memory instead of the repository
+        let model = fs::read("../../../models/addsub/1/model.py").unwrap();
+        let options = LoadOptions {
+            config_json: Some(config.to_string()),
+            files: HashMap::from([("1/model.py".to_string(), model)]),
+        };
+        server.load_model_with("addsub", options).unwrap();
+        assert!(server.is_model_ready("addsub", 1).unwrap());
+    }
+
+    #[test]
+    #[ignore = "needs Triton and the test model repository"]
+    fn test_register_repository() {
+        // a second repository holding a copy of addsub under another directory
+        let repository = std::env::temp_dir().join(format!("rustis-models-{}", std::process::id()));
+        let version = repository.join("team_addsub").join("1");
+        fs::create_dir_all(&version).unwrap();
+        fs::write(
+            repository.join("team_addsub").join("config.pbtxt"),
+            "backend: \"python\"\nmax_batch_size: 0\n",
+        )
+        .unwrap();
+        fs::copy(
+            "../../../models/addsub/1/model.py",
+            version.join("model.py"),
+        )
+        .unwrap();
+        let path = repository.to_str().unwrap();
+        let mapping = HashMap::from([("team_addsub".to_string(), "addsub_v2".to_string())]);
+
+        let server = test_server();
+        server.register_model_repository(path, &mapping).unwrap();
+        let index = server.model_index(false).unwrap();
+        assert!(index.iter().any(|e| e.name == "addsub_v2"));
+        assert!(!index.iter().any(|e| e.name == "team_addsub"));
+        server.load_model("addsub_v2").unwrap();
+        assert!(server.is_model_ready("addsub_v2", 1).unwrap());
+        server.unregister_model_repository(path).unwrap();
+        assert!(server.unregister_model_repository(path).is_err());
+        // only allowed in the poll model control mode
+        assert!(server.poll_model_repository().is_err());
+        fs::remove_dir_all(&repository).unwrap();
+    }
+
+    #[test]
+    #[ignore = "needs Triton and the test model repository"]
+    fn test_custom_metrics() {
+        let server = test_server();
+        let family = MetricFamily::new(
+            MetricKind::Counter,
+            "rustis_test_requests",
+            "requests seen by the test",
+        )
+        .unwrap();
+        let metric = family.metric(&[("route", "test")]).unwrap();
+        metric.increment(2.0).unwrap();
+        assert_eq!(metric.value().unwrap(), 2.0);
+        assert!(metric.increment(-1.0).is_err());
+        assert!(metric.set(1.0).is_err());
+
+        let text = server.metrics().unwrap().formatted().unwrap();
+        assert!(text.contains("rustis_test_requests{route=\"test\"} 2"));
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_stream_addsub() {
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "addsub".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![
+                infer_proto::model_infer_request::InferInputTensor {
+                    name: "INPUT0".to_string(),
+                    datatype: "FP32".to_string(),
+                    shape: vec![4],
+                    contents: Some(InferTensorContents {
+                        fp32_contents: vec![1.0, 2.0, 3.0, 4.0],
+                        ..Default::default()
+                    }),
                     parameters: HashMap::new(),
-                    model_name: "uint8".to_string(),
-                    model_version: "-1".to_owned(),
-                    id: "1".to_string(),
-                    inputs: vec![infer_proto::model_infer_request::InferInputTensor {
-                        name: "INPUT".to_string(),
-                        datatype: "UINT8".to_string(),
-                        shape: vec![4],
-                        contents: Some(InferTensorContents {
-                            uint_contents: vec![1, 2, 3, 4],
-                            ..Default::default()
-                        }),
-                        parameters: HashMap::new(),
-                    }],
-                    outputs: vec![
-                        infer_proto::model_infer_request::InferRequestedOutputTensor {
-                            name: "OUTPUT".to_string(),
-                            parameters: HashMap::new(),
-                        },
-                    ],
-                    raw_input_contents: vec![],
-                };
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
+                },
+                infer_proto::model_infer_request::InferInputTensor {
+                    name: "INPUT1".to_string(),
+                    datatype: "FP32".to_string(),
+                    shape: vec![4],
+                    contents: Some(InferTensorContents {
+                        fp32_contents: vec![1.0, 2.0, 3.0, 4.0],
+                        ..Default::default()
+                    }),
+                    parameters: HashMap::new(),
+                },
+            ],
+            outputs: vec![],
+            raw_input_contents: vec![],
+        };
+
+        let server = test_server();
+        server.load_model("addsub").unwrap();
+
+        let (sender, mut receiver) = mpsc::channel(4);
+        let resp = server
+            .infer_stream(Box::new(request), 5_000_000, None, &sender, |r| r)
+            .await;
+        assert!(resp.is_ok());
+        drop(sender);
+
+        let mut responses = vec![];
+        while let Some(r) = receiver.recv().await {
+            responses.push(r);
+        }
+        assert_eq!(responses.len(), 1);
+        match &responses[0] {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => {
+                    assert_eq!(content.fp32_contents, vec![2.0, 4.0, 6.0, 8.0])
                 }
-            }
-            Err(e) => error!("{:?}", e.msg()),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
         }
     }
 
     #[tokio::test]
-    async fn test_uint16() {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_request_builder() {
+        use crate::wrapper::inference_request::InferRequestBuilder;
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("uint16") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let fp32 = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32;
+        let builder = InferRequestBuilder::new("addsub")
+            .id("1")
+            .input("INPUT0", fp32, &[4], data.clone())
+            .input("INPUT1", fp32, &[4], data)
+            .output("OUTPUT0")
+            .timeout_micro_seconds(5_000_000);
 
-                let request = infer_proto::ModelInferRequest {
-                    parameters: HashMap::new(),
-                    model_name: "uint16".to_string(),
-                    model_version: "-1".to_owned(),
-                    id: "1".to_string(),
-                    inputs: vec![infer_proto::model_infer_request::InferInputTensor {
-                        name: "INPUT".to_string(),
-                        datatype: "UINT16".to_string(),
-                        shape: vec![4],
-                        contents: Some(InferTensorContents {
-                            uint_contents: vec![1, 2, 3, 4],
-                            ..Default::default()
-                        }),
-                        parameters: HashMap::new(),
-                    }],
-                    outputs: vec![
-                        infer_proto::model_infer_request::InferRequestedOutputTensor {
-                            name: "OUTPUT".to_string(),
-                            parameters: HashMap::new(),
-                        },
-                    ],
-                    raw_input_contents: vec![],
-                };
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
+        let server = test_server();
+        server.load_model("addsub").unwrap();
+
+        // dropped before submission, the request and its data are freed
+        drop(builder.clone().build(&server).unwrap());
+
+        let request = builder.build(&server).unwrap();
+        assert_eq!(request.id().unwrap(), "1");
+        let mut submitted = server.infer_async(request).unwrap();
+        let response = submitted.receiver.as_mut().unwrap().recv().await.unwrap();
+        assert!(response.error().is_none());
+        let output = response.output(0).unwrap();
+        assert_eq!(output.name(), "OUTPUT0");
+        let sum = unsafe { std::slice::from_raw_parts(output.base() as *const f32, output.len()) };
+        assert_eq!(sum, &[2.0, 4.0, 6.0, 8.0]);
+
+        // refused by Triton, the request is deleted on return
+        let missing = InferRequestBuilder::new("missing").build(&server);
+        if let Ok(request) = missing {
+            assert!(server.infer_async(request).is_err());
+        }
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_concurrent_infer() {
+        use crate::wrapper::inference_request::InferRequestBuilder;
+        const REQUESTS: usize = 64;
+
+        let server = std::sync::Arc::new(test_server());
+        server.load_model("addsub").unwrap();
+        // responses queue up while the runtime is busy submitting, Triton's
+        // threads must never wait for them to be read
+        let fp32 = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32;
+        let mut tasks = Vec::with_capacity(REQUESTS);
+        for i in 0..REQUESTS {
+            let server = server.clone();
+            tasks.push(tokio::spawn(async move {
+                let data: Vec<u8> = [i as f32; 4].iter().flat_map(|v| v.to_le_bytes()).collect();
+                let request = InferRequestBuilder::new("addsub")
+                    .input("INPUT0", fp32, &[4], data.clone())
+                    .input("INPUT1", fp32, &[4], data)
+                    .output("OUTPUT0")
+                    .build(&server)
+                    .unwrap();
+                let mut receiver = server.infer_async(request).unwrap().receiver.unwrap();
+                let response = receiver.recv().await.unwrap();
+                assert!(response.error().is_none());
+                {
+                    let output = response.output(0).unwrap();
+                    let sum = unsafe {
+                        std::slice::from_raw_parts(output.base() as *const f32, output.len())
+                    };
+                    assert_eq!(sum, &[2.0 * i as f32; 4]);
                 }
+                drop(response);
+                // the final callback freed the sender, which closes the channel
+                assert!(receiver.recv().await.is_none());
+            }));
+        }
+        for task in tasks {
+            task.await.unwrap();
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_trace() {
+        use crate::wrapper::inference_request::InferRequestBuilder;
+        use crate::wrapper::trace::{InferenceTrace, Span, SpanExporter, TraceLevel};
+        use std::sync::{mpsc as std_mpsc, Arc, Mutex};
+        use std::time::Duration;
+
+        struct Collect(Mutex<std_mpsc::Sender<Vec<Span>>>);
+
+        impl SpanExporter for Collect {
+            fn export(&self, spans: Vec<Span>) {
+                let _ = self.0.lock().unwrap().send(spans);
             }
-            Err(e) => error!("{:?}", e.msg()),
         }
+
+        let data: Vec<u8> = [1.0f32, 2.0, 3.0, 4.0]
+            .iter()
+            .flat_map(|v| v.to_le_bytes())
+            .collect();
+        let fp32 = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32;
+        let builder = InferRequestBuilder::new("addsub")
+            .input("INPUT0", fp32, &[4], data.clone())
+            .input("INPUT1", fp32, &[4], data);
+        let (sender, receiver) = std_mpsc::channel();
+        let exporter = Arc::new(Collect(Mutex::new(sender)));
+
+        let server = test_server();
+        server.load_model("addsub").unwrap();
+
+        // never submitted, the trace is deleted without exporting anything
+        let mut request = builder.clone().build(&server).unwrap();
+        let trace = InferenceTrace::new(TraceLevel::All, None, exporter.clone());
+        request.set_trace(trace.unwrap());
+        drop(request);
+
+        let mut request = builder.build(&server).unwrap();
+        let trace = InferenceTrace::new(TraceLevel::All, None, exporter).unwrap();
+        let trace_id = trace.trace_id();
+        request.set_trace(trace);
+        let mut submitted = server.infer_async(request).unwrap();
+        let response = submitted.receiver.as_mut().unwrap().recv().await.unwrap();
+        assert!(response.error().is_none());
+
+        let spans = receiver.recv_timeout(Duration::from_secs(5)).unwrap();
+        assert!(spans.iter().all(|s| s.trace_id == trace_id));
+        assert_eq!(spans[0].name, "addsub");
+        assert_eq!(spans[0].parent_span_id, None);
+        let names: Vec<&str> = spans.iter().map(|s| s.name.as_str()).collect();
+        assert!(names.contains(&"queue") && names.contains(&"compute"));
+        let tensors = spans[0]
+            .events
+            .iter()
+            .filter(|e| e.name.starts_with("TENSOR_"));
+        assert!(tensors.count() > 0);
+        assert!(receiver.try_recv().is_err());
     }
 
     #[tokio::test]
-    async fn test_uint32() {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_infer_tensors() {
+        use ndarray::{ArrayD, IxDyn};
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("uint32") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
+        let server = test_server();
+        server.load_model("addsub").unwrap();
 
-                let request = infer_proto::ModelInferRequest {
-                    parameters: HashMap::new(),
-                    model_name: "uint32".to_string(),
-                    model_version: "-1".to_owned(),
-                    id: "1".to_string(),
-                    inputs: vec![infer_proto::model_infer_request::InferInputTensor {
-                        name: "INPUT".to_string(),
-                        datatype: "UINT32".to_string(),
-                        shape: vec![4],
-                        contents: Some(InferTensorContents {
-                            uint_contents: vec![1, 2, 3, 4],
-                            ..Default::default()
-                        }),
-                        parameters: HashMap::new(),
-                    }],
-                    outputs: vec![
-                        infer_proto::model_infer_request::InferRequestedOutputTensor {
-                            name: "OUTPUT".to_string(),
-                            parameters: HashMap::new(),
-                        },
-                    ],
-                    raw_input_contents: vec![],
-                };
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
-                }
+        let input = ArrayD::from_shape_vec(IxDyn(&[4]), vec![1.0f32, 2.0, 3.0, 4.0]);
+        let input = Tensor::from(input.unwrap());
+        let inputs = HashMap::from([
+            ("INPUT0".to_string(), input.clone()),
+            ("INPUT1".to_string(), input),
+        ]);
+        match server.infer_tensors("addsub", -1, inputs).await {
+            Ok(mut outputs) => {
+                let sum = ArrayD::<f32>::try_from(outputs.remove("OUTPUT0").unwrap());
+                assert_eq!(sum.unwrap().into_raw_vec(), vec![2.0, 4.0, 6.0, 8.0]);
+                let difference = outputs.remove("OUTPUT1").unwrap();
+                assert_eq!(difference.shape(), vec![4]);
             }
-            Err(e) => error!("{:?}", e.msg()),
+            Err(e) => panic!("{:?}", e),
         }
     }
 
     #[tokio::test]
-    async fn test_fp16() {
-        let options = ServerOptions::new().unwrap();
-        // let mut //project_root = get_project_root().unwrap();
-        //project_root.push("models");
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_uint8() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("uint8").unwrap();
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "uint8".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![infer_proto::model_infer_request::InferInputTensor {
+                name: "INPUT".to_string(),
+                datatype: "UINT8".to_string(),
+                shape: vec![4],
+                contents: Some(InferTensorContents {
+                    uint_contents: vec![1, 2, 3, 4],
+                    ..Default::default()
+                }),
+                parameters: HashMap::new(),
+            }],
+            outputs: vec![
+                infer_proto::model_infer_request::InferRequestedOutputTensor {
+                    name: "OUTPUT".to_string(),
+                    parameters: HashMap::new(),
+                },
+            ],
+            raw_input_contents: vec![],
+        };
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
+        }
+    }
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("fp16") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
+    #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_uint16() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("uint16").unwrap();
 
-                let request = infer_proto::ModelInferRequest {
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "uint16".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![infer_proto::model_infer_request::InferInputTensor {
+                name: "INPUT".to_string(),
+                datatype: "UINT16".to_string(),
+                shape: vec![4],
+                contents: Some(InferTensorContents {
+                    uint_contents: vec![1, 2, 3, 4],
+                    ..Default::default()
+                }),
+                parameters: HashMap::new(),
+            }],
+            outputs: vec![
+                infer_proto::model_infer_request::InferRequestedOutputTensor {
+                    name: "OUTPUT".to_string(),
                     parameters: HashMap::new(),
-                    model_name: "pf16".to_string(),
-                    model_version: "-1".to_owned(),
-                    id: "1".to_string(),
-                    inputs: vec![infer_proto::model_infer_request::InferInputTensor {
-                        name: "INPUT".to_string(),
-                        datatype: "FP16".to_string(),
-                        shape: vec![4],
-                        contents: Some(InferTensorContents {
-                            fp32_contents: vec![1., 2., 3., 4.],
-                            ..Default::default()
-                        }),
-                        parameters: HashMap::new(),
-                    }],
-                    outputs: vec![
-                        infer_proto::model_infer_request::InferRequestedOutputTensor {
-                            name: "OUTPUT".to_string(),
-                            parameters: HashMap::new(),
-                        },
-                    ],
-                    raw_input_contents: vec![],
-                };
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => assert_eq!(content.fp32_contents, vec![1., 2., 3., 4.]),
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
-                }
-            }
-            Err(e) => error!("{:?}", e.msg()),
+                },
+            ],
+            raw_input_contents: vec![],
+        };
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
         }
     }
 
     #[tokio::test]
-    async fn test_bytes() {
-        let options = ServerOptions::new().unwrap();
-        options.set_model_repository_path("../../../models").unwrap();
-        let model_control_mode =
-            tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT;
-        options.set_model_control_mode(model_control_mode).unwrap();
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_uint32() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("uint32").unwrap();
 
-        let server = Server::new(options);
-        match server {
-            Ok(server) => {
-                assert!(server.is_live().unwrap());
-                assert!(server.is_ready().unwrap());
-                match server.load_model("bytes") {
-                    Ok(_) => info!("model loading successful"),
-                    Err(e) => error!("{:?}", e.msg()),
-                }
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "uint32".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![infer_proto::model_infer_request::InferInputTensor {
+                name: "INPUT".to_string(),
+                datatype: "UINT32".to_string(),
+                shape: vec![4],
+                contents: Some(InferTensorContents {
+                    uint_contents: vec![1, 2, 3, 4],
+                    ..Default::default()
+                }),
+                parameters: HashMap::new(),
+            }],
+            outputs: vec![
+                infer_proto::model_infer_request::InferRequestedOutputTensor {
+                    name: "OUTPUT".to_string(),
+                    parameters: HashMap::new(),
+                },
+            ],
+            raw_input_contents: vec![],
+        };
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => assert_eq!(content.uint_contents, vec![1, 2, 3, 4]),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_fp16() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("fp16").unwrap();
 
-                let request = infer_proto::ModelInferRequest {
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "fp16".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![infer_proto::model_infer_request::InferInputTensor {
+                name: "INPUT".to_string(),
+                datatype: "FP16".to_string(),
+                shape: vec![4],
+                contents: None,
+                parameters: HashMap::new(),
+            }],
+            outputs: vec![
+                infer_proto::model_infer_request::InferRequestedOutputTensor {
+                    name: "OUTPUT".to_string(),
                     parameters: HashMap::new(),
-                    model_name: "bytes".to_string(),
-                    model_version: "-1".to_owned(),
-                    id: "1".to_string(),
-                    inputs: vec![infer_proto::model_infer_request::InferInputTensor {
-                        name: "INPUT".to_string(),
-                        datatype: "BYTES".to_string(),
-                        shape: vec![4],
-                        contents: Some(InferTensorContents {
-                            bytes_contents: vec![
-                                "stringa".as_bytes().to_vec(),
-                                "stringb".as_bytes().to_vec(),
-                                "stringc".as_bytes().to_vec(),
-                                "stringd".as_bytes().to_vec(),
-                            ],
-                            ..Default::default()
-                        }),
-                        parameters: HashMap::new(),
-                    }],
-                    outputs: vec![
-                        infer_proto::model_infer_request::InferRequestedOutputTensor {
-                            name: "OUTPUT".to_string(),
-                            parameters: HashMap::new(),
-                        },
+                },
+            ],
+            // 1.0, 2.0, 3.0 and 4.0 as little-endian half floats
+            raw_input_contents: vec![vec![0x00, 0x3c, 0x00, 0x40, 0x00, 0x42, 0x00, 0x44]],
+        };
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => assert_eq!(
+                r.raw_output_contents,
+                vec![vec![0x00, 0x3c, 0x00, 0x40, 0x00, 0x42, 0x00, 0x44]]
+            ),
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[tokio::test]
+    #[ignore = "needs Triton and the test model repository"]
+    async fn test_bytes() {
+        let server = test_server();
+        assert!(server.is_live().unwrap());
+        assert!(server.is_ready().unwrap());
+        server.load_model("bytes").unwrap();
+
+        let request = infer_proto::ModelInferRequest {
+            parameters: HashMap::new(),
+            model_name: "bytes".to_string(),
+            model_version: "-1".to_owned(),
+            id: "1".to_string(),
+            inputs: vec![infer_proto::model_infer_request::InferInputTensor {
+                name: "INPUT".to_string(),
+                datatype: "BYTES".to_string(),
+                shape: vec![4],
+                contents: Some(InferTensorContents {
+                    bytes_contents: vec![
+                        "stringa".as_bytes().to_vec(),
+                        "stringb".as_bytes().to_vec(),
+                        "stringc".as_bytes().to_vec(),
+                        "stringd".as_bytes().to_vec(),
                     ],
-                    raw_input_contents: vec![],
-                };
-                let resp = server.infer(Box::new(request), 5_000_000).await;
-                info!("resp is {:?}", resp);
-                match resp {
-                    Ok(r) => match &r.outputs[0].contents {
-                        Some(content) => assert_eq!(
-                            content.bytes_contents,
-                            vec![
-                                b"stringa".to_vec(),
-                                b"stringb".to_vec(),
-                                b"stringc".to_vec(),
-                                b"stringd".to_vec()
-                            ]
-                        ),
-                        None => assert_eq!(true, false),
-                    },
-                    Err(e) => error!("Error: {:?}", e),
-                }
-            }
-            Err(e) => error!("{:?}", e.msg()),
+                    ..Default::default()
+                }),
+                parameters: HashMap::new(),
+            }],
+            outputs: vec![
+                infer_proto::model_infer_request::InferRequestedOutputTensor {
+                    name: "OUTPUT".to_string(),
+                    parameters: HashMap::new(),
+                },
+            ],
+            raw_input_contents: vec![],
+        };
+        let resp = server.infer(Box::new(request), 5_000_000, None).await;
+        info!("resp is {:?}", resp);
+        match resp {
+            Ok(r) => match &r.outputs[0].contents {
+                Some(content) => assert_eq!(
+                    content.bytes_contents,
+                    vec![
+                        b"stringa".to_vec(),
+                        b"stringb".to_vec(),
+                        b"stringc".to_vec(),
+                        b"stringd".to_vec()
+                    ]
+                ),
+                None => assert_eq!(true, false),
+            },
+            Err(e) => panic!("{:?}", e),
+        }
+    }
+
+    #[test]
+    fn test_load_options() {
+        let config = r#"{"backend": "python"}"#.to_string();
+        let file = |path: &str| HashMap::from([(path.to_string(), vec![0u8])]);
+        let valid = LoadOptions {
+            config_json: Some(config.clone()),
+            files: file("1/model.py"),
+        };
+        assert!(valid.validate().is_ok());
+        assert!(LoadOptions::default().validate().is_ok());
+
+        for invalid in [
+            LoadOptions {
+                config_json: None,
+                files: file("1/model.py"),
+            },
+            LoadOptions {
+                config_json: Some("backend: python".to_string()),
+                ..Default::default()
+            },
+            LoadOptions {
+                config_json: Some(config.clone()),
+                files: file("../other/1/model.py"),
+            },
+            LoadOptions {
+                config_json: Some(config),
+                files: file("/1/model.py"),
+            },
+        ] {
+            assert!(invalid.validate().is_err(), "{:?}", invalid);
         }
     }
+
+    #[test]
+    fn test_aggregate_responses() {
+        let response = |token: &str, raw: bool| {
+            let contents = infer_proto::InferTensorContents {
+                bytes_contents: vec![token.as_bytes().to_vec()],
+                ..Default::default()
+            };
+            infer_proto::ModelInferResponse {
+                model_name: "generate".to_string(),
+                model_version: "1".to_string(),
+                outputs: vec![infer_proto::model_infer_response::InferOutputTensor {
+                    name: "text_output".to_string(),
+                    datatype: "BYTES".to_string(),
+                    shape: vec![1],
+                    contents: (!raw).then_some(contents),
+                    ..Default::default()
+                }],
+                raw_output_contents: match raw {
+                    true => vec![[&[token.len() as u8, 0, 0, 0], token.as_bytes()].concat()],
+                    false => vec![],
+                },
+                ..Default::default()
+            }
+        };
+        let no_shared_memory = HashSet::new();
+        let aggregate = |responses, raw| aggregate_responses(responses, raw, &no_shared_memory);
+        assert_eq!(aggregate(vec![], false).unwrap(), None);
+
+        let aggregated = aggregate(vec![response("a", false), response("bc", false)], false);
+        let output = &aggregated.unwrap().unwrap().outputs[0];
+        assert_eq!(output.shape, vec![2]);
+        assert_eq!(
+            output.contents.as_ref().unwrap().bytes_contents,
+            vec![b"a".to_vec(), b"bc".to_vec()]
+        );
+
+        // the final response of a decoupled model often has no outputs
+        let empty = infer_proto::ModelInferResponse::default();
+        let aggregated = aggregate(vec![response("a", true), response("bc", true), empty], true)
+            .unwrap()
+            .unwrap();
+        assert_eq!(aggregated.model_name, "generate");
+        assert_eq!(aggregated.outputs[0].shape, vec![2]);
+        assert_eq!(
+            aggregated.raw_output_contents,
+            vec![vec![1, 0, 0, 0, b'a', 2, 0, 0, 0, b'b', b'c']]
+        );
+
+        assert!(aggregate(vec![response("a", true), response("b", false)], false).is_err());
+        let mut scalar = response("b", false);
+        scalar.outputs[0].shape = vec![];
+        assert!(aggregate(vec![response("a", false), scalar], false).is_err());
+
+        // outputs in shared memory have an empty raw entry and keep the last response
+        let in_shared_memory = |token: &str| {
+            let mut r = response(token, true);
+            r.outputs[0].shape = vec![token.len() as i64];
+            r.raw_output_contents = vec![vec![]];
+            r
+        };
+        let shared_memory = HashSet::from(["text_output".to_string()]);
+        let aggregated = aggregate_responses(
+            vec![in_shared_memory("a"), in_shared_memory("bc")],
+            true,
+            &shared_memory,
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(aggregated.outputs[0].shape, vec![2]);
+        assert_eq!(aggregated.raw_output_contents, vec![Vec::<u8>::new()]);
+        assert!("aggregate".parse::<DecoupledModels>().is_ok());
+        assert!("first".parse::<DecoupledModels>().is_err());
+    }
+
+    #[test]
+    fn test_sequence_parameters() {
+        use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+        let parameter = |choice| infer_proto::InferParameter {
+            parameter_choice: Some(choice),
+        };
+        let mut parameters = HashMap::new();
+        assert_eq!(
+            SequenceParameters::parse(&parameters).unwrap(),
+            SequenceParameters { id: None, flags: 0 }
+        );
+
+        let bool_parameter = |v| parameter(ParameterChoice::BoolParam(v));
+        parameters.insert("sequence_id".to_string(), parameter(ParameterChoice::Int64Param(42)));
+        parameters.insert("sequence_start".to_string(), bool_parameter(true));
+        parameters.insert("sequence_end".to_string(), bool_parameter(false));
+        assert_eq!(
+            SequenceParameters::parse(&parameters).unwrap(),
+            SequenceParameters {
+                id: Some(CorrelationId::Int(42)),
+                flags: tritonserver_requestflag_enum_TRITONSERVER_REQUEST_FLAG_SEQUENCE_START,
+            }
+        );
+
+        parameters.insert(
+            "sequence_id".to_string(),
+            parameter(ParameterChoice::StringParam("session-1".to_string())),
+        );
+        parameters.insert("sequence_end".to_string(), bool_parameter(true));
+        let sequence = SequenceParameters::parse(&parameters).unwrap();
+        assert_eq!(sequence.id, Some(CorrelationId::String("session-1".to_string())));
+        assert_eq!(
+            sequence.flags,
+            tritonserver_requestflag_enum_TRITONSERVER_REQUEST_FLAG_SEQUENCE_START
+                | tritonserver_requestflag_enum_TRITONSERVER_REQUEST_FLAG_SEQUENCE_END
+        );
+
+        parameters.insert("sequence_id".to_string(), parameter(ParameterChoice::Int64Param(-1)));
+        assert!(SequenceParameters::parse(&parameters).is_err());
+        parameters.insert("sequence_id".to_string(), parameter(ParameterChoice::Int64Param(1)));
+        parameters.insert("sequence_end".to_string(), parameter(ParameterChoice::Int64Param(1)));
+        assert!(SequenceParameters::parse(&parameters).is_err());
+    }
+
+    #[test]
+    fn test_request_parameters() {
+        use ::infer_proto::infer_proto::infer_parameter::ParameterChoice;
+        use ::infer_proto::infer_proto::model_infer_request::InferRequestedOutputTensor;
+        let parameter = |choice| infer_proto::InferParameter {
+            parameter_choice: Some(choice),
+        };
+        let mut pb_request = infer_proto::ModelInferRequest::default();
+        pb_request
+            .parameters
+            .insert("priority".to_string(), parameter(ParameterChoice::Int64Param(2)));
+        pb_request
+            .parameters
+            .insert("timeout".to_string(), parameter(ParameterChoice::Int64Param(1000)));
+        let parameters = RequestParameters::parse(&pb_request.parameters).unwrap();
+        assert_eq!(
+            parameters,
+            RequestParameters {
+                priority: Some(2),
+                timeout: Some(1000),
+                binary_data_output: None,
+            }
+        );
+        assert!(!raw_output(&pb_request, &parameters).unwrap());
+
+        let output = |binary_data: Option<bool>| {
+            let mut parameters = HashMap::new();
+            if let Some(v) = binary_data {
+                let binary_data = parameter(ParameterChoice::BoolParam(v));
+                parameters.insert("binary_data".to_string(), binary_data);
+            }
+            InferRequestedOutputTensor {
+                name: "OUTPUT".to_string(),
+                parameters,
+            }
+        };
+        let parameters = RequestParameters {
+            binary_data_output: Some(true),
+            ..Default::default()
+        };
+        pb_request.outputs = vec![output(None), output(Some(true))];
+        assert!(raw_output(&pb_request, &parameters).unwrap());
+        pb_request.outputs = vec![output(None), output(Some(false))];
+        assert!(raw_output(&pb_request, &parameters).is_err());
+        pb_request.outputs = vec![output(Some(false))];
+        assert!(!raw_output(&pb_request, &parameters).unwrap());
+
+        pb_request
+            .parameters
+            .insert("priority".to_string(), parameter(ParameterChoice::Int64Param(1 << 40)));
+        assert!(RequestParameters::parse(&pb_request.parameters).is_err());
+        assert_eq!("reject".parse::<UnknownParameters>().unwrap(), UnknownParameters::Reject);
+        assert!("forward".parse::<UnknownParameters>().is_err());
+    }
 }
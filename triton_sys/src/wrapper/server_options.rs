@@ -1,64 +1,242 @@
-use std::{ffi::CString, fmt::Error, ptr};
+use std::{ptr, time::Duration};
 
 use crate::sys::*;
 
-use super::error::TritonError;
+use super::error::{c_string, TritonError};
 
 pub struct ServerOptions {
     pub _options: *mut TRITONSERVER_ServerOptions,
 }
 
 impl ServerOptions {
-    pub fn new() -> Result<Self, Error> {
+    pub fn new() -> Result<Self, TritonError> {
         let mut options = ptr::null_mut() as *mut TRITONSERVER_ServerOptions;
         let err = unsafe {
             TRITONSERVER_ServerOptionsNew(&mut options as *mut *mut TRITONSERVER_ServerOptions)
         };
-        // TODO: error handling
         if !err.is_null() {
-            return Err(Error);
+            return Err(TritonError::from_ptr(err));
         }
         Ok(ServerOptions { _options: options })
     }
 
-    pub fn set_model_repository_path(&self, path: &str) -> Result<(), Error> {
-        let c_path = CString::new(path).unwrap();
-        let err = unsafe {
+    pub fn set_server_id(&self, server_id: &str) -> Result<(), TritonError> {
+        let c_server_id = c_string("server id", server_id)?;
+        check(unsafe { TRITONSERVER_ServerOptionsSetServerId(self._options, c_server_id.as_ptr()) })
+    }
+
+    /// Adds a model repository, can be called once per repository.
+    pub fn set_model_repository_path(&self, path: &str) -> Result<(), TritonError> {
+        let c_path = c_string("model repository path", path)?;
+        check(unsafe {
             TRITONSERVER_ServerOptionsSetModelRepositoryPath(self._options, c_path.as_ptr())
-        };
-        if !err.is_null() {
-            return Err(Error);
-        }
-        Ok(())
+        })
     }
 
-    pub fn set_backend_config(&self, backend: &str, key: &str, value: &str) -> Result<(), Error> {
-        let c_backend = CString::new(backend).unwrap();
-        let c_key = CString::new(key).unwrap();
-        let c_value = CString::new(value).unwrap();
-        let err = unsafe {
+    pub fn set_backend_config(
+        &self,
+        backend: &str,
+        key: &str,
+        value: &str,
+    ) -> Result<(), TritonError> {
+        let c_backend = c_string("backend name", backend)?;
+        let c_key = c_string("backend setting", key)?;
+        let c_value = c_string("backend setting value", value)?;
+        check(unsafe {
             TRITONSERVER_ServerOptionsSetBackendConfig(
                 self._options,
                 c_backend.as_ptr(),
                 c_key.as_ptr(),
                 c_value.as_ptr(),
             )
-        };
-        if !err.is_null() {
-            return Err(Error);
-        }
-        Ok(())
+        })
     }
 
     pub fn set_model_control_mode(
         &self,
         mode: TRITONSERVER_ModelControlMode,
     ) -> Result<(), TritonError> {
-        let err = unsafe { TRITONSERVER_ServerOptionsSetModelControlMode(self._options, mode) };
-        if !err.is_null() {
-            return Err(TritonError::from_ptr(err));
-        }
-        Ok(())
+        check(unsafe { TRITONSERVER_ServerOptionsSetModelControlMode(self._options, mode) })
+    }
+
+    /// Adds a model to load on startup, only used in explicit model control mode.
+    pub fn set_startup_model(&self, model_name: &str) -> Result<(), TritonError> {
+        let c_model_name = c_string("startup model", model_name)?;
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetStartupModel(self._options, c_model_name.as_ptr())
+        })
+    }
+
+    pub fn set_strict_model_config(&self, strict: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetStrictModelConfig(self._options, strict) })
+    }
+
+    pub fn set_strict_readiness(&self, strict: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetStrictReadiness(self._options, strict) })
+    }
+
+    pub fn set_exit_on_error(&self, exit: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetExitOnError(self._options, exit) })
+    }
+
+    pub fn set_exit_timeout(&self, seconds: u32) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetExitTimeout(self._options, seconds) })
+    }
+
+    pub fn set_rate_limiter_mode(
+        &self,
+        mode: TRITONSERVER_RateLimitMode,
+    ) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetRateLimiterMode(self._options, mode) })
+    }
+
+    /// Makes `count` units of resource `name` available to the rate limiter
+    /// on `device`, -1 meaning every device.
+    pub fn add_rate_limiter_resource(
+        &self,
+        name: &str,
+        count: usize,
+        device: i32,
+    ) -> Result<(), TritonError> {
+        let c_name = c_string("rate limiter resource", name)?;
+        check(unsafe {
+            TRITONSERVER_ServerOptionsAddRateLimiterResource(
+                self._options,
+                c_name.as_ptr(),
+                count,
+                device,
+            )
+        })
+    }
+
+    pub fn set_pinned_memory_pool_byte_size(&self, size: u64) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetPinnedMemoryPoolByteSize(self._options, size) })
+    }
+
+    pub fn set_cuda_memory_pool_byte_size(
+        &self,
+        gpu_device: i32,
+        size: u64,
+    ) -> Result<(), TritonError> {
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetCudaMemoryPoolByteSize(self._options, gpu_device, size)
+        })
+    }
+
+    pub fn set_response_cache_byte_size(&self, size: u64) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetResponseCacheByteSize(self._options, size) })
+    }
+
+    pub fn set_min_supported_compute_capability(&self, cc: f64) -> Result<(), TritonError> {
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetMinSupportedComputeCapability(self._options, cc)
+        })
+    }
+
+    pub fn set_buffer_manager_thread_count(&self, thread_count: u32) -> Result<(), TritonError> {
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetBufferManagerThreadCount(self._options, thread_count)
+        })
+    }
+
+    pub fn set_model_load_thread_count(&self, thread_count: u32) -> Result<(), TritonError> {
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetModelLoadThreadCount(self._options, thread_count)
+        })
+    }
+
+    /// Writes the Triton log to `file`, an empty path logs to the console.
+    pub fn set_log_file(&self, file: &str) -> Result<(), TritonError> {
+        let c_file = c_string("log file", file)?;
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogFile(self._options, c_file.as_ptr()) })
+    }
+
+    pub fn set_log_info(&self, log: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogInfo(self._options, log) })
+    }
+
+    pub fn set_log_warn(&self, log: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogWarn(self._options, log) })
+    }
+
+    pub fn set_log_error(&self, log: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogError(self._options, log) })
+    }
+
+    pub fn set_log_format(&self, format: TRITONSERVER_LogFormat) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogFormat(self._options, format) })
+    }
+
+    /// Sets the verbose logging level, zero disables verbose logging.
+    pub fn set_log_verbose(&self, level: i32) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetLogVerbose(self._options, level) })
+    }
+
+    pub fn set_metrics(&self, metrics: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetMetrics(self._options, metrics) })
+    }
+
+    pub fn set_gpu_metrics(&self, gpu_metrics: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetGpuMetrics(self._options, gpu_metrics) })
+    }
+
+    pub fn set_cpu_metrics(&self, cpu_metrics: bool) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetCpuMetrics(self._options, cpu_metrics) })
+    }
+
+    pub fn set_metrics_interval(&self, interval_ms: u64) -> Result<(), TritonError> {
+        check(unsafe { TRITONSERVER_ServerOptionsSetMetricsInterval(self._options, interval_ms) })
+    }
+
+    pub fn set_backend_directory(&self, backend_dir: &str) -> Result<(), TritonError> {
+        let c_backend_dir = c_string("backend directory", backend_dir)?;
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetBackendDirectory(self._options, c_backend_dir.as_ptr())
+        })
+    }
+
+    pub fn set_repo_agent_directory(&self, repoagent_dir: &str) -> Result<(), TritonError> {
+        let c_repoagent_dir = c_string("repository agent directory", repoagent_dir)?;
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetRepoAgentDirectory(self._options, c_repoagent_dir.as_ptr())
+        })
+    }
+
+    /// Rejects model loads on a device once its memory usage exceeds
+    /// `fraction`, Triton only supports GPU devices.
+    pub fn set_model_load_device_limit(
+        &self,
+        kind: TRITONSERVER_InstanceGroupKind,
+        device_id: i32,
+        fraction: f64,
+    ) -> Result<(), TritonError> {
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetModelLoadDeviceLimit(
+                self._options,
+                kind,
+                device_id,
+                fraction,
+            )
+        })
+    }
+
+    pub fn set_host_policy(
+        &self,
+        policy_name: &str,
+        setting: &str,
+        value: &str,
+    ) -> Result<(), TritonError> {
+        let c_policy_name = c_string("host policy name", policy_name)?;
+        let c_setting = c_string("host policy setting", setting)?;
+        let c_value = c_string("host policy value", value)?;
+        check(unsafe {
+            TRITONSERVER_ServerOptionsSetHostPolicy(
+                self._options,
+                c_policy_name.as_ptr(),
+                c_setting.as_ptr(),
+                c_value.as_ptr(),
+            )
+        })
     }
 }
 
@@ -69,3 +247,486 @@ impl Drop for ServerOptions {
         }
     }
 }
+
+fn check(err: *mut TRITONSERVER_Error) -> Result<(), TritonError> {
+    if !err.is_null() {
+        return Err(TritonError::from_ptr(err));
+    }
+    Ok(())
+}
+
+fn invalid_arg(msg: String) -> TritonError {
+    TritonError::new(
+        TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_INVALID_ARG,
+        msg,
+    )
+}
+
+/// How the models of the repositories are loaded.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModelControlMode {
+    /// Every model is loaded on startup, repository changes are ignored.
+    None,
+    /// Every model is loaded on startup, then the repositories are polled for changes.
+    Poll,
+    /// Models are only loaded on startup or on request.
+    Explicit,
+}
+
+impl From<ModelControlMode> for TRITONSERVER_ModelControlMode {
+    fn from(mode: ModelControlMode) -> Self {
+        match mode {
+            ModelControlMode::None => {
+                tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_NONE
+            }
+            ModelControlMode::Poll => {
+                tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_POLL
+            }
+            ModelControlMode::Explicit => {
+                tritonserver_modelcontrolmode_enum_TRITONSERVER_MODEL_CONTROL_EXPLICIT
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RateLimitMode {
+    Off,
+    /// Prioritizes the model instances that ran the least.
+    ExecCount,
+}
+
+impl From<RateLimitMode> for TRITONSERVER_RateLimitMode {
+    fn from(mode: RateLimitMode) -> Self {
+        match mode {
+            RateLimitMode::Off => tritonserver_ratelimitmode_enum_TRITONSERVER_RATE_LIMIT_OFF,
+            RateLimitMode::ExecCount => {
+                tritonserver_ratelimitmode_enum_TRITONSERVER_RATE_LIMIT_EXEC_COUNT
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Default,
+    Iso8601,
+}
+
+impl From<LogFormat> for TRITONSERVER_LogFormat {
+    fn from(format: LogFormat) -> Self {
+        match format {
+            LogFormat::Default => TRITONSERVER_logformat_enum_TRITONSERVER_LOG_DEFAULT,
+            LogFormat::Iso8601 => TRITONSERVER_logformat_enum_TRITONSERVER_LOG_ISO8601,
+        }
+    }
+}
+
+/// Collects server options and validates them before creating the
+/// `ServerOptions`. Unset options keep the Triton defaults.
+#[derive(Debug, Clone, Default)]
+pub struct ServerOptionsBuilder {
+    server_id: Option<String>,
+    model_repository_paths: Vec<String>,
+    model_control_mode: Option<ModelControlMode>,
+    startup_models: Vec<String>,
+    strict_model_config: Option<bool>,
+    strict_readiness: Option<bool>,
+    exit_on_error: Option<bool>,
+    exit_timeout: Option<Duration>,
+    rate_limiter_mode: Option<RateLimitMode>,
+    rate_limiter_resources: Vec<(String, usize, i32)>,
+    pinned_memory_pool_byte_size: Option<u64>,
+    cuda_memory_pool_byte_sizes: Vec<(i32, u64)>,
+    response_cache_byte_size: Option<u64>,
+    min_supported_compute_capability: Option<f64>,
+    buffer_manager_thread_count: Option<u32>,
+    model_load_thread_count: Option<u32>,
+    log_file: Option<String>,
+    log_info: Option<bool>,
+    log_warn: Option<bool>,
+    log_error: Option<bool>,
+    log_format: Option<LogFormat>,
+    log_verbose: Option<i32>,
+    metrics: Option<bool>,
+    gpu_metrics: Option<bool>,
+    cpu_metrics: Option<bool>,
+    metrics_interval: Option<Duration>,
+    backend_directory: Option<String>,
+    repo_agent_directory: Option<String>,
+    backend_configs: Vec<(String, String, String)>,
+    host_policies: Vec<(String, String, String)>,
+    model_load_gpu_limits: Vec<(i32, f64)>,
+}
+
+impl ServerOptionsBuilder {
+    pub fn new() -> Self {
+        ServerOptionsBuilder::default()
+    }
+
+    pub fn server_id(mut self, server_id: &str) -> Self {
+        self.server_id = Some(server_id.to_owned());
+        self
+    }
+
+    /// Adds a model repository, models must be unique across repositories.
+    pub fn model_repository_path(mut self, path: &str) -> Self {
+        self.model_repository_paths.push(path.to_owned());
+        self
+    }
+
+    pub fn model_control_mode(mut self, mode: ModelControlMode) -> Self {
+        self.model_control_mode = Some(mode);
+        self
+    }
+
+    /// Adds a model to load on startup, which requires the explicit model control mode.
+    pub fn startup_model(mut self, model_name: &str) -> Self {
+        self.startup_models.push(model_name.to_owned());
+        self
+    }
+
+    pub fn strict_model_config(mut self, strict: bool) -> Self {
+        self.strict_model_config = Some(strict);
+        self
+    }
+
+    pub fn strict_readiness(mut self, strict: bool) -> Self {
+        self.strict_readiness = Some(strict);
+        self
+    }
+
+    pub fn exit_on_error(mut self, exit: bool) -> Self {
+        self.exit_on_error = Some(exit);
+        self
+    }
+
+    /// Sets how long the server waits for in-flight requests on exit, in whole seconds.
+    pub fn exit_timeout(mut self, timeout: Duration) -> Self {
+        self.exit_timeout = Some(timeout);
+        self
+    }
+
+    pub fn rate_limiter_mode(mut self, mode: RateLimitMode) -> Self {
+        self.rate_limiter_mode = Some(mode);
+        self
+    }
+
+    /// Adds `count` units of resource `name` on `device`, `None` meaning every device.
+    pub fn rate_limiter_resource(mut self, name: &str, count: usize, device: Option<u32>) -> Self {
+        let device = device.map_or(-1, |d| d as i32);
+        self.rate_limiter_resources
+            .push((name.to_owned(), count, device));
+        self
+    }
+
+    pub fn pinned_memory_pool_byte_size(mut self, size: u64) -> Self {
+        self.pinned_memory_pool_byte_size = Some(size);
+        self
+    }
+
+    pub fn cuda_memory_pool_byte_size(mut self, gpu_device: u32, size: u64) -> Self {
+        self.cuda_memory_pool_byte_sizes
+            .push((gpu_device as i32, size));
+        self
+    }
+
+    /// Enables the response cache with `size` bytes of CPU memory.
+    pub fn response_cache_byte_size(mut self, size: u64) -> Self {
+        self.response_cache_byte_size = Some(size);
+        self
+    }
+
+    pub fn min_supported_compute_capability(mut self, cc: f64) -> Self {
+        self.min_supported_compute_capability = Some(cc);
+        self
+    }
+
+    pub fn buffer_manager_thread_count(mut self, thread_count: u32) -> Self {
+        self.buffer_manager_thread_count = Some(thread_count);
+        self
+    }
+
+    pub fn model_load_thread_count(mut self, thread_count: u32) -> Self {
+        self.model_load_thread_count = Some(thread_count);
+        self
+    }
+
+    pub fn log_file(mut self, file: &str) -> Self {
+        self.log_file = Some(file.to_owned());
+        self
+    }
+
+    pub fn log_info(mut self, log: bool) -> Self {
+        self.log_info = Some(log);
+        self
+    }
+
+    pub fn log_warn(mut self, log: bool) -> Self {
+        self.log_warn = Some(log);
+        self
+    }
+
+    pub fn log_error(mut self, log: bool) -> Self {
+        self.log_error = Some(log);
+        self
+    }
+
+    pub fn log_format(mut self, format: LogFormat) -> Self {
+        self.log_format = Some(format);
+        self
+    }
+
+    pub fn log_verbose(mut self, level: u32) -> Self {
+        self.log_verbose = Some(level as i32);
+        self
+    }
+
+    pub fn metrics(mut self, metrics: bool) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn gpu_metrics(mut self, gpu_metrics: bool) -> Self {
+        self.gpu_metrics = Some(gpu_metrics);
+        self
+    }
+
+    pub fn cpu_metrics(mut self, cpu_metrics: bool) -> Self {
+        self.cpu_metrics = Some(cpu_metrics);
+        self
+    }
+
+    pub fn metrics_interval(mut self, interval: Duration) -> Self {
+        self.metrics_interval = Some(interval);
+        self
+    }
+
+    pub fn backend_directory(mut self, backend_dir: &str) -> Self {
+        self.backend_directory = Some(backend_dir.to_owned());
+        self
+    }
+
+    pub fn repo_agent_directory(mut self, repoagent_dir: &str) -> Self {
+        self.repo_agent_directory = Some(repoagent_dir.to_owned());
+        self
+    }
+
+    pub fn backend_config(mut self, backend: &str, key: &str, value: &str) -> Self {
+        self.backend_configs
+            .push((backend.to_owned(), key.to_owned(), value.to_owned()));
+        self
+    }
+
+    pub fn host_policy(mut self, policy_name: &str, setting: &str, value: &str) -> Self {
+        self.host_policies
+            .push((policy_name.to_owned(), setting.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Rejects model loads on GPU `device_id` once `fraction` of its memory is used.
+    pub fn model_load_gpu_limit(mut self, device_id: u32, fraction: f64) -> Self {
+        self.model_load_gpu_limits
+            .push((device_id as i32, fraction));
+        self
+    }
+
+    /// Checks the options that Triton would only reject when the server starts.
+    pub fn validate(&self) -> Result<(), TritonError> {
+        if self.model_repository_paths.is_empty() {
+            return Err(invalid_arg(
+                "at least one model repository path is required".to_string(),
+            ));
+        }
+        if !self.startup_models.is_empty()
+            && self.model_control_mode != Some(ModelControlMode::Explicit)
+        {
+            return Err(invalid_arg(
+                "startup models require the explicit model control mode".to_string(),
+            ));
+        }
+        if self.metrics == Some(false)
+            && (self.gpu_metrics == Some(true) || self.cpu_metrics == Some(true))
+        {
+            return Err(invalid_arg(
+                "GPU and CPU metrics cannot be enabled with metrics disabled".to_string(),
+            ));
+        }
+        if self.metrics_interval.is_some_and(|i| i.as_millis() == 0) {
+            return Err(invalid_arg(
+                "metrics interval must be at least one millisecond".to_string(),
+            ));
+        }
+        if self.model_load_thread_count == Some(0) {
+            return Err(invalid_arg(
+                "model load thread count must be positive".to_string(),
+            ));
+        }
+        if let Some(timeout) = self.exit_timeout {
+            if timeout.as_secs() > u32::MAX as u64 {
+                return Err(invalid_arg("exit timeout is too long".to_string()));
+            }
+            if timeout.subsec_nanos() != 0 {
+                return Err(invalid_arg(format!(
+                    "exit timeout must be whole seconds, got {:?}",
+                    timeout
+                )));
+            }
+        }
+        if let Some(cc) = self.min_supported_compute_capability {
+            if !cc.is_finite() || cc < 0.0 {
+                return Err(invalid_arg(format!(
+                    "invalid minimum supported compute capability {}",
+                    cc
+                )));
+            }
+        }
+        if let Some(level) = self.log_verbose {
+            if level < 0 {
+                return Err(invalid_arg(format!("invalid verbose log level {}", level)));
+            }
+        }
+        for (device_id, fraction) in &self.model_load_gpu_limits {
+            if !(0.0..=1.0).contains(fraction) {
+                return Err(invalid_arg(format!(
+                    "model load limit of GPU {} must be a fraction between 0 and 1, got {}",
+                    device_id, fraction
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(self) -> Result<ServerOptions, TritonError> {
+        self.validate()?;
+        let options = ServerOptions::new()?;
+        if let Some(server_id) = &self.server_id {
+            options.set_server_id(server_id)?;
+        }
+        for path in &self.model_repository_paths {
+            options.set_model_repository_path(path)?;
+        }
+        if let Some(mode) = self.model_control_mode {
+            options.set_model_control_mode(mode.into())?;
+        }
+        for model_name in &self.startup_models {
+            options.set_startup_model(model_name)?;
+        }
+        if let Some(strict) = self.strict_model_config {
+            options.set_strict_model_config(strict)?;
+        }
+        if let Some(strict) = self.strict_readiness {
+            options.set_strict_readiness(strict)?;
+        }
+        if let Some(exit) = self.exit_on_error {
+            options.set_exit_on_error(exit)?;
+        }
+        if let Some(timeout) = self.exit_timeout {
+            options.set_exit_timeout(timeout.as_secs() as u32)?;
+        }
+        if let Some(mode) = self.rate_limiter_mode {
+            options.set_rate_limiter_mode(mode.into())?;
+        }
+        for (name, count, device) in &self.rate_limiter_resources {
+            options.add_rate_limiter_resource(name, *count, *device)?;
+        }
+        if let Some(size) = self.pinned_memory_pool_byte_size {
+            options.set_pinned_memory_pool_byte_size(size)?;
+        }
+        for (gpu_device, size) in &self.cuda_memory_pool_byte_sizes {
+            options.set_cuda_memory_pool_byte_size(*gpu_device, *size)?;
+        }
+        if let Some(size) = self.response_cache_byte_size {
+            options.set_response_cache_byte_size(size)?;
+        }
+        if let Some(cc) = self.min_supported_compute_capability {
+            options.set_min_supported_compute_capability(cc)?;
+        }
+        if let Some(thread_count) = self.buffer_manager_thread_count {
+            options.set_buffer_manager_thread_count(thread_count)?;
+        }
+        if let Some(thread_count) = self.model_load_thread_count {
+            options.set_model_load_thread_count(thread_count)?;
+        }
+        if let Some(file) = &self.log_file {
+            options.set_log_file(file)?;
+        }
+        if let Some(log) = self.log_info {
+            options.set_log_info(log)?;
+        }
+        if let Some(log) = self.log_warn {
+            options.set_log_warn(log)?;
+        }
+        if let Some(log) = self.log_error {
+            options.set_log_error(log)?;
+        }
+        if let Some(format) = self.log_format {
+            options.set_log_format(format.into())?;
+        }
+        if let Some(level) = self.log_verbose {
+            options.set_log_verbose(level)?;
+        }
+        if let Some(metrics) = self.metrics {
+            options.set_metrics(metrics)?;
+        }
+        if let Some(gpu_metrics) = self.gpu_metrics {
+            options.set_gpu_metrics(gpu_metrics)?;
+        }
+        if let Some(cpu_metrics) = self.cpu_metrics {
+            options.set_cpu_metrics(cpu_metrics)?;
+        }
+        if let Some(interval) = self.metrics_interval {
+            options.set_metrics_interval(interval.as_millis() as u64)?;
+        }
+        if let Some(backend_dir) = &self.backend_directory {
+            options.set_backend_directory(backend_dir)?;
+        }
+        if let Some(repoagent_dir) = &self.repo_agent_directory {
+            options.set_repo_agent_directory(repoagent_dir)?;
+        }
+        for (backend, key, value) in &self.backend_configs {
+            options.set_backend_config(backend, key, value)?;
+        }
+        for (policy_name, setting, value) in &self.host_policies {
+            options.set_host_policy(policy_name, setting, value)?;
+        }
+        for (device_id, fraction) in &self.model_load_gpu_limits {
+            options.set_model_load_device_limit(
+                TRITONSERVER_instancegroupkind_enum_TRITONSERVER_INSTANCEGROUPKIND_GPU,
+                *device_id,
+                *fraction,
+            )?;
+        }
+        Ok(options)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builder_validation() {
+        let builder = ServerOptionsBuilder::new()
+            .model_repository_path("../../../models")
+            .model_control_mode(ModelControlMode::Explicit)
+            .startup_model("addsub")
+            .metrics_interval(Duration::from_secs(1))
+            .model_load_gpu_limit(0, 0.8);
+        assert!(builder.validate().is_ok());
+
+        assert!(ServerOptionsBuilder::new().validate().is_err());
+        let invalid = [
+            builder.clone().model_control_mode(ModelControlMode::Poll),
+            builder.clone().metrics(false).gpu_metrics(true),
+            builder.clone().metrics_interval(Duration::from_micros(10)),
+            builder.clone().model_load_thread_count(0),
+            builder.clone().exit_timeout(Duration::from_millis(500)),
+            builder.clone().min_supported_compute_capability(f64::NAN),
+            builder.model_load_gpu_limit(1, 1.5),
+        ];
+        for builder in invalid {
+            assert!(builder.validate().is_err(), "{:?}", builder);
+        }
+    }
+}
@@ -0,0 +1,236 @@
+use crate::sys::*;
+use std::{
+    collections::HashMap,
+    ffi::c_void,
+    ptr,
+    sync::{Arc, Mutex},
+};
+
+use super::error::{c_string, Result, RustisError};
+
+/// A system (POSIX) shared memory region mapped into the server process.
+/// Requests keep the region alive through an `Arc` so that unregistering it
+/// while inferences are in flight does not unmap their memory.
+#[derive(Debug)]
+pub struct SharedMemoryRegion {
+    name: String,
+    key: String,
+    offset: usize,
+    byte_size: usize,
+    mapping: *mut c_void,
+    mapping_size: usize,
+}
+
+impl SharedMemoryRegion {
+    pub fn open(name: &str, key: &str, offset: usize, byte_size: usize) -> Result<Self> {
+        let c_key = c_string("shared memory key", key)?;
+        // mmap offsets must be page aligned, map from the start and skip `offset` instead
+        let mapping_size = offset.checked_add(byte_size).ok_or_else(|| {
+            RustisError::Validation(format!(
+                "invalid offset + byte size for shared memory region: '{}'",
+                name
+            ))
+        })?;
+        let fd =
+            unsafe { libc::shm_open(c_key.as_ptr(), libc::O_RDWR, libc::S_IRUSR | libc::S_IWUSR) };
+        if fd == -1 {
+            return Err(RustisError::Internal(format!(
+                "unable to open shared memory region '{}' with key '{}': {}",
+                name,
+                key,
+                std::io::Error::last_os_error()
+            )));
+        }
+        // pages past the end of the object raise SIGBUS on access
+        let mut stat: libc::stat = unsafe { std::mem::zeroed() };
+        if unsafe { libc::fstat(fd, &mut stat) } == -1 {
+            let fstat_err = std::io::Error::last_os_error();
+            unsafe {
+                libc::close(fd);
+            }
+            return Err(RustisError::Internal(format!(
+                "unable to stat shared memory region '{}': {}",
+                name, fstat_err
+            )));
+        }
+        if (stat.st_size as u64) < mapping_size as u64 {
+            unsafe {
+                libc::close(fd);
+            }
+            return Err(RustisError::Validation(format!(
+                "offset + byte size {} exceeds the {} bytes of shared memory region '{}'",
+                mapping_size, stat.st_size, name
+            )));
+        }
+        let mapping = unsafe {
+            libc::mmap(
+                ptr::null_mut(),
+                mapping_size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            )
+        };
+        let mmap_err = std::io::Error::last_os_error();
+        unsafe {
+            libc::close(fd);
+        }
+        if mapping == libc::MAP_FAILED {
+            return Err(RustisError::Internal(format!(
+                "unable to map shared memory region '{}': {}",
+                name, mmap_err
+            )));
+        }
+        Ok(SharedMemoryRegion {
+            name: name.to_owned(),
+            key: key.to_owned(),
+            offset,
+            byte_size,
+            mapping,
+            mapping_size,
+        })
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    pub fn offset(&self) -> usize {
+        self.offset
+    }
+
+    pub fn byte_size(&self) -> usize {
+        self.byte_size
+    }
+
+    /// Returns a pointer to `byte_size` bytes starting `offset` bytes into the
+    /// region, or an error if the range is not inside the region.
+    pub fn slice(&self, offset: usize, byte_size: usize) -> Result<*mut u8> {
+        match offset.checked_add(byte_size) {
+            Some(end) if end <= self.byte_size => {}
+            _ => {
+                return Err(RustisError::Validation(format!(
+                    "invalid offset + byte size for shared memory region: '{}'",
+                    self.name
+                )))
+            }
+        }
+        Ok(unsafe { (self.mapping as *mut u8).add(self.offset + offset) })
+    }
+}
+
+impl Drop for SharedMemoryRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.mapping, self.mapping_size);
+        }
+    }
+}
+
+unsafe impl Send for SharedMemoryRegion {}
+unsafe impl Sync for SharedMemoryRegion {}
+
+/// Registry of the system shared memory regions known to the server.
+#[derive(Default)]
+pub struct SharedMemoryManager {
+    regions: Mutex<HashMap<String, Arc<SharedMemoryRegion>>>,
+}
+
+impl SharedMemoryManager {
+    pub fn register(&self, name: &str, key: &str, offset: usize, byte_size: usize) -> Result<()> {
+        let mut regions = self.regions.lock().unwrap();
+        if regions.contains_key(name) {
+            return Err(RustisError::Triton {
+                code: TRITONSERVER_errorcode_enum_TRITONSERVER_ERROR_ALREADY_EXISTS,
+                msg: format!("shared memory region '{}' already in manager", name),
+            });
+        }
+        let region = SharedMemoryRegion::open(name, key, offset, byte_size)?;
+        regions.insert(name.to_owned(), Arc::new(region));
+        Ok(())
+    }
+
+    /// Unregisters `name`, or every region when `name` is empty.
+    pub fn unregister(&self, name: &str) {
+        let mut regions = self.regions.lock().unwrap();
+        if name.is_empty() {
+            regions.clear();
+        } else {
+            regions.remove(name);
+        }
+    }
+
+    pub fn get(&self, name: &str) -> Result<Arc<SharedMemoryRegion>> {
+        match self.regions.lock().unwrap().get(name) {
+            Some(region) => Ok(region.clone()),
+            None => Err(RustisError::NotFound(format!(
+                "unable to find shared memory region: '{}'",
+                name
+            ))),
+        }
+    }
+
+    /// Returns the status of `name`, or of every region when `name` is empty.
+    pub fn status(&self, name: &str) -> Result<Vec<Arc<SharedMemoryRegion>>> {
+        if name.is_empty() {
+            let regions = self.regions.lock().unwrap();
+            return Ok(regions.values().cloned().collect());
+        }
+        Ok(vec![self.get(name)?])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ffi::CString;
+
+    #[test]
+    fn test_register_region() {
+        let key = CString::new("/rustis_test_register_region").unwrap();
+        unsafe {
+            let fd = libc::shm_open(
+                key.as_ptr(),
+                libc::O_RDWR | libc::O_CREAT,
+                libc::S_IRUSR | libc::S_IWUSR,
+            );
+            assert_ne!(fd, -1);
+            assert_eq!(libc::ftruncate(fd, 64), 0);
+            libc::close(fd);
+        }
+
+        let manager = SharedMemoryManager::default();
+        manager
+            .register("input", "/rustis_test_register_region", 16, 32)
+            .unwrap();
+        assert!(manager
+            .register("input", "/rustis_test_register_region", 0, 8)
+            .is_err());
+
+        let region = manager.get("input").unwrap();
+        assert_eq!(region.byte_size(), 32);
+        assert!(region.slice(0, 32).is_ok());
+        assert!(region.slice(16, 17).is_err());
+
+        // past the end of the 64 byte object, and overflowing
+        for (offset, byte_size) in [(16, 64), (1, usize::MAX)] {
+            let registered =
+                manager.register("output", "/rustis_test_register_region", offset, byte_size);
+            assert!(matches!(registered, Err(RustisError::Validation(_))));
+        }
+        assert_eq!(manager.status("").unwrap().len(), 1);
+
+        manager.unregister("");
+        assert!(manager.get("input").is_err());
+        // the region stays mapped while a request still holds it
+        unsafe {
+            *region.slice(0, 1).unwrap() = 1;
+            libc::shm_unlink(key.as_ptr());
+        }
+    }
+}
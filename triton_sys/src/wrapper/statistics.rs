@@ -0,0 +1,130 @@
+use infer_proto::infer_proto;
+use serde::Deserialize;
+
+/// Statistics returned by TRITONSERVER_ServerModelStatistics, one entry per
+/// model version.
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct ModelStatistics {
+    pub name: String,
+    pub version: String,
+    /// Timestamp of the last inference request, in milliseconds since the epoch.
+    pub last_inference: u64,
+    pub inference_count: u64,
+    pub execution_count: u64,
+    pub inference_stats: InferStatistics,
+    pub batch_stats: Vec<InferBatchStatistics>,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct InferStatistics {
+    pub success: StatisticDuration,
+    pub fail: StatisticDuration,
+    pub queue: StatisticDuration,
+    pub compute_input: StatisticDuration,
+    pub compute_infer: StatisticDuration,
+    pub compute_output: StatisticDuration,
+    pub cache_hit: StatisticDuration,
+    pub cache_miss: StatisticDuration,
+}
+
+#[derive(Debug, Default, Clone, Deserialize)]
+#[serde(default)]
+pub struct InferBatchStatistics {
+    pub batch_size: u64,
+    pub compute_input: StatisticDuration,
+    pub compute_infer: StatisticDuration,
+    pub compute_output: StatisticDuration,
+}
+
+/// A count and the cumulative duration in nanoseconds.
+#[derive(Debug, Default, Clone, Copy, Deserialize)]
+#[serde(default)]
+pub struct StatisticDuration {
+    pub count: u64,
+    pub ns: u64,
+}
+
+#[derive(Deserialize)]
+struct ModelStatisticsList {
+    model_stats: Vec<ModelStatistics>,
+}
+
+pub fn parse_model_statistics(json: &str) -> serde_json::Result<Vec<ModelStatistics>> {
+    let list: ModelStatisticsList = serde_json::from_str(json)?;
+    Ok(list.model_stats)
+}
+
+impl From<StatisticDuration> for infer_proto::StatisticDuration {
+    fn from(duration: StatisticDuration) -> Self {
+        infer_proto::StatisticDuration {
+            count: duration.count,
+            ns: duration.ns,
+        }
+    }
+}
+
+impl From<InferStatistics> for infer_proto::InferStatistics {
+    fn from(stats: InferStatistics) -> Self {
+        infer_proto::InferStatistics {
+            success: Some(stats.success.into()),
+            fail: Some(stats.fail.into()),
+            queue: Some(stats.queue.into()),
+            compute_input: Some(stats.compute_input.into()),
+            compute_infer: Some(stats.compute_infer.into()),
+            compute_output: Some(stats.compute_output.into()),
+            cache_hit: Some(stats.cache_hit.into()),
+            cache_miss: Some(stats.cache_miss.into()),
+        }
+    }
+}
+
+impl From<InferBatchStatistics> for infer_proto::InferBatchStatistics {
+    fn from(stats: InferBatchStatistics) -> Self {
+        infer_proto::InferBatchStatistics {
+            batch_size: stats.batch_size,
+            compute_input: Some(stats.compute_input.into()),
+            compute_infer: Some(stats.compute_infer.into()),
+            compute_output: Some(stats.compute_output.into()),
+        }
+    }
+}
+
+impl From<ModelStatistics> for infer_proto::ModelStatistics {
+    fn from(stats: ModelStatistics) -> Self {
+        infer_proto::ModelStatistics {
+            name: stats.name,
+            version: stats.version,
+            last_inference: stats.last_inference,
+            inference_count: stats.inference_count,
+            execution_count: stats.execution_count,
+            inference_stats: Some(stats.inference_stats.into()),
+            batch_stats: stats.batch_stats.into_iter().map(Into::into).collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_model_statistics() {
+        let json = r#"{"model_stats":[{"name":"addsub","version":"1","last_inference":1700000000000,
+            "inference_count":4,"execution_count":2,
+            "inference_stats":{"success":{"count":4,"ns":4000},"fail":{"count":0,"ns":0},
+            "queue":{"count":4,"ns":400},"compute_input":{"count":4,"ns":40},
+            "compute_infer":{"count":4,"ns":3000},"compute_output":{"count":4,"ns":40}},
+            "batch_stats":[{"batch_size":2,"compute_input":{"count":2,"ns":40},
+            "compute_infer":{"count":2,"ns":3000},"compute_output":{"count":2,"ns":40}}]}]}"#;
+        let stats = parse_model_statistics(json).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].name, "addsub");
+        assert_eq!(stats[0].inference_count, 4);
+        assert_eq!(stats[0].inference_stats.queue.ns, 400);
+        // cache statistics are only reported when the response cache is enabled
+        assert_eq!(stats[0].inference_stats.cache_hit.count, 0);
+        assert_eq!(stats[0].batch_stats[0].batch_size, 2);
+    }
+}
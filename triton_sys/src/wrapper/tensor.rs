@@ -0,0 +1,319 @@
+//! Typed tensors backed by `ndarray`, to run inference in process without
+//! going through protobuf.
+use half::{bf16, f16};
+use ndarray::{ArrayD, IxDyn};
+
+use crate::sys::*;
+
+use super::classification::serialize_bytes;
+use super::error::{Result, RustisError};
+use super::inference_response::InferResponseOutput;
+use super::utils;
+
+/// A tensor of one of the Triton datatypes. BYTES elements are arbitrary
+/// byte strings.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Tensor {
+    Bool(ArrayD<bool>),
+    Uint8(ArrayD<u8>),
+    Uint16(ArrayD<u16>),
+    Uint32(ArrayD<u32>),
+    Uint64(ArrayD<u64>),
+    Int8(ArrayD<i8>),
+    Int16(ArrayD<i16>),
+    Int32(ArrayD<i32>),
+    Int64(ArrayD<i64>),
+    Fp16(ArrayD<f16>),
+    Fp32(ArrayD<f32>),
+    Fp64(ArrayD<f64>),
+    Bytes(ArrayD<Vec<u8>>),
+    Bf16(ArrayD<bf16>),
+}
+
+/// Calls `$f` with the array of any variant of `$tensor`.
+macro_rules! with_array {
+    ($tensor:expr, $array:ident => $f:expr) => {
+        match $tensor {
+            Tensor::Bool($array) => $f,
+            Tensor::Uint8($array) => $f,
+            Tensor::Uint16($array) => $f,
+            Tensor::Uint32($array) => $f,
+            Tensor::Uint64($array) => $f,
+            Tensor::Int8($array) => $f,
+            Tensor::Int16($array) => $f,
+            Tensor::Int32($array) => $f,
+            Tensor::Int64($array) => $f,
+            Tensor::Fp16($array) => $f,
+            Tensor::Fp32($array) => $f,
+            Tensor::Fp64($array) => $f,
+            Tensor::Bytes($array) => $f,
+            Tensor::Bf16($array) => $f,
+        }
+    };
+}
+
+impl Tensor {
+    pub fn datatype(&self) -> TRITONSERVER_DataType {
+        match self {
+            Tensor::Bool(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BOOL,
+            Tensor::Uint8(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT8,
+            Tensor::Uint16(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT16,
+            Tensor::Uint32(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT32,
+            Tensor::Uint64(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT64,
+            Tensor::Int8(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT8,
+            Tensor::Int16(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT16,
+            Tensor::Int32(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32,
+            Tensor::Int64(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT64,
+            Tensor::Fp16(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP16,
+            Tensor::Fp32(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32,
+            Tensor::Fp64(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP64,
+            Tensor::Bytes(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BYTES,
+            Tensor::Bf16(_) => TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BF16,
+        }
+    }
+
+    pub fn shape(&self) -> Vec<i64> {
+        with_array!(self, a => a.shape().iter().map(|&d| d as i64).collect())
+    }
+
+    /// Serializes the elements in row-major order as Triton expects them,
+    /// little-endian, with BYTES elements prefixed by their 4-byte length.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        match self {
+            Tensor::Bytes(a) => serialize_bytes(&a.iter().collect::<Vec<_>>()),
+            Tensor::Bool(a) => encode(a),
+            Tensor::Uint8(a) => encode(a),
+            Tensor::Uint16(a) => encode(a),
+            Tensor::Uint32(a) => encode(a),
+            Tensor::Uint64(a) => encode(a),
+            Tensor::Int8(a) => encode(a),
+            Tensor::Int16(a) => encode(a),
+            Tensor::Int32(a) => encode(a),
+            Tensor::Int64(a) => encode(a),
+            Tensor::Fp16(a) => encode(a),
+            Tensor::Fp32(a) => encode(a),
+            Tensor::Fp64(a) => encode(a),
+            Tensor::Bf16(a) => encode(a),
+        }
+    }
+
+    /// Reads a tensor serialized as by `to_bytes`.
+    #[allow(non_upper_case_globals)]
+    pub fn from_bytes(datatype: TRITONSERVER_DataType, shape: &[i64], data: &[u8]) -> Result<Self> {
+        if shape.iter().any(|&d| d < 0) {
+            return Err(RustisError::Validation(format!(
+                "invalid tensor shape {:?}",
+                shape
+            )));
+        }
+        let shape = IxDyn(&shape.iter().map(|&d| d as usize).collect::<Vec<_>>());
+        let tensor = match datatype {
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BOOL => Tensor::Bool(decode(shape, data)?),
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT8 => {
+                Tensor::Uint8(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT16 => {
+                Tensor::Uint16(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT32 => {
+                Tensor::Uint32(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_UINT64 => {
+                Tensor::Uint64(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT8 => Tensor::Int8(decode(shape, data)?),
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT16 => {
+                Tensor::Int16(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32 => {
+                Tensor::Int32(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT64 => {
+                Tensor::Int64(decode(shape, data)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP16 => Tensor::Fp16(decode(shape, data)?),
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP32 => Tensor::Fp32(decode(shape, data)?),
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_FP64 => Tensor::Fp64(decode(shape, data)?),
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BYTES => {
+                Tensor::Bytes(shaped(shape, deserialize_bytes(data)?)?)
+            }
+            TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BF16 => Tensor::Bf16(decode(shape, data)?),
+            _ => {
+                return Err(RustisError::Validation(format!(
+                    "unsupported tensor datatype {}",
+                    utils::data_type_to_string(datatype)
+                )))
+            }
+        };
+        Ok(tensor)
+    }
+
+    /// Copies an output of an inference response.
+    pub fn from_output(output: &InferResponseOutput) -> Result<Self> {
+        let data = match output.byte_size() {
+            0 => &[],
+            byte_size => unsafe {
+                std::slice::from_raw_parts(output.base() as *const u8, byte_size)
+            },
+        };
+        Tensor::from_bytes(output.datatype(), output.shape(), data)
+    }
+}
+
+/// Element types with a fixed size little-endian encoding.
+trait Element: Sized {
+    const SIZE: usize;
+    fn read(bytes: &[u8]) -> Self;
+    fn write(&self, data: &mut Vec<u8>);
+}
+
+macro_rules! element {
+    ($($t:ty),*) => {
+        $(
+            impl Element for $t {
+                const SIZE: usize = std::mem::size_of::<$t>();
+                fn read(bytes: &[u8]) -> Self {
+                    <$t>::from_le_bytes(bytes.try_into().unwrap())
+                }
+                fn write(&self, data: &mut Vec<u8>) {
+                    data.extend_from_slice(&self.to_le_bytes());
+                }
+            }
+        )*
+    };
+}
+
+element!(u8, u16, u32, u64, i8, i16, i32, i64, f16, f32, f64, bf16);
+
+impl Element for bool {
+    const SIZE: usize = 1;
+    fn read(bytes: &[u8]) -> Self {
+        bytes[0] != 0
+    }
+    fn write(&self, data: &mut Vec<u8>) {
+        data.push(*self as u8);
+    }
+}
+
+fn encode<T: Element>(array: &ArrayD<T>) -> Vec<u8> {
+    let mut data = Vec::with_capacity(array.len() * T::SIZE);
+    for v in array.iter() {
+        v.write(&mut data);
+    }
+    data
+}
+
+fn decode<T: Element>(shape: IxDyn, data: &[u8]) -> Result<ArrayD<T>> {
+    if data.len() % T::SIZE != 0 {
+        return Err(RustisError::Validation(format!(
+            "tensor data of {} bytes is not a multiple of the element size {}",
+            data.len(),
+            T::SIZE
+        )));
+    }
+    shaped(shape, data.chunks_exact(T::SIZE).map(T::read).collect())
+}
+
+fn shaped<T>(shape: IxDyn, elements: Vec<T>) -> Result<ArrayD<T>> {
+    ArrayD::from_shape_vec(shape, elements)
+        .map_err(|e| RustisError::Validation(format!("invalid tensor data: {}", e)))
+}
+
+/// Splits BYTES tensor data into its length-prefixed elements.
+fn deserialize_bytes(mut data: &[u8]) -> Result<Vec<Vec<u8>>> {
+    let mut elements = vec![];
+    while !data.is_empty() {
+        let invalid = || RustisError::Validation("truncated BYTES tensor data".to_string());
+        let len = data.get(..4).ok_or_else(invalid)?;
+        let len = u32::from_le_bytes(len.try_into().unwrap()) as usize;
+        let element = data.get(4..4 + len).ok_or_else(invalid)?;
+        elements.push(element.to_vec());
+        data = &data[4 + len..];
+    }
+    Ok(elements)
+}
+
+macro_rules! conversions {
+    ($($variant:ident($t:ty)),*) => {
+        $(
+            impl From<ArrayD<$t>> for Tensor {
+                fn from(array: ArrayD<$t>) -> Self {
+                    Tensor::$variant(array)
+                }
+            }
+
+            impl TryFrom<Tensor> for ArrayD<$t> {
+                type Error = RustisError;
+
+                fn try_from(tensor: Tensor) -> Result<Self, Self::Error> {
+                    match tensor {
+                        Tensor::$variant(array) => Ok(array),
+                        tensor => Err(RustisError::Validation(format!(
+                            "tensor of type {} is not {}",
+                            utils::data_type_to_string(tensor.datatype()),
+                            stringify!($t)
+                        ))),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+conversions!(
+    Bool(bool),
+    Uint8(u8),
+    Uint16(u16),
+    Uint32(u32),
+    Uint64(u64),
+    Int8(i8),
+    Int16(i16),
+    Int32(i32),
+    Int64(i64),
+    Fp16(f16),
+    Fp32(f32),
+    Fp64(f64),
+    Bytes(Vec<u8>),
+    Bf16(bf16)
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tensor_bytes() {
+        let fp32 = ArrayD::from_shape_vec(IxDyn(&[2, 2]), vec![1.0f32, 2.0, 3.0, 4.0]).unwrap();
+        let tensor = Tensor::from(fp32.clone());
+        assert_eq!(tensor.shape(), vec![2, 2]);
+        let data = tensor.to_bytes();
+        assert_eq!(data.len(), 16);
+        let decoded = Tensor::from_bytes(tensor.datatype(), &tensor.shape(), &data).unwrap();
+        assert_eq!(ArrayD::<f32>::try_from(decoded).unwrap(), fp32);
+        // transposed arrays are serialized in logical order
+        let transposed = Tensor::from(fp32.t().to_owned()).to_bytes();
+        assert_eq!(&transposed[4..8], &3.0f32.to_le_bytes());
+
+        let fp16 = ArrayD::from_shape_vec(IxDyn(&[2]), vec![f16::from_f32(1.0), f16::ONE]).unwrap();
+        assert_eq!(Tensor::from(fp16).to_bytes(), vec![0x00, 0x3c, 0x00, 0x3c]);
+
+        let bytes = ArrayD::from_shape_vec(IxDyn(&[2]), vec![b"ab".to_vec(), vec![]]).unwrap();
+        let data = Tensor::from(bytes.clone()).to_bytes();
+        assert_eq!(data, b"\x02\x00\x00\x00ab\x00\x00\x00\x00".to_vec());
+        let datatype = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BYTES;
+        let decoded = Tensor::from_bytes(datatype, &[2], &data).unwrap();
+        assert_eq!(decoded, Tensor::Bytes(bytes));
+        assert!(Tensor::from_bytes(datatype, &[2], &data[..5]).is_err());
+
+        let bool_type = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_BOOL;
+        let decoded = Tensor::from_bytes(bool_type, &[3], &[1, 0, 1]).unwrap();
+        assert!(ArrayD::<i32>::try_from(decoded.clone()).is_err());
+        assert_eq!(
+            ArrayD::<bool>::try_from(decoded).unwrap().into_raw_vec(),
+            vec![true, false, true]
+        );
+        let int32_type = TRITONSERVER_datatype_enum_TRITONSERVER_TYPE_INT32;
+        assert!(Tensor::from_bytes(int32_type, &[2], &[0; 6]).is_err());
+        assert!(Tensor::from_bytes(int32_type, &[3], &[0; 8]).is_err());
+    }
+}
@@ -0,0 +1,753 @@
+//! Inference tracing. Triton reports the timeline, and optionally the
+//! tensors, of a traced request to callbacks, which turn it into spans handed
+//! to a `SpanExporter` once Triton releases the trace.
+use super::error::{RustisError, TritonError};
+use super::utils;
+use crate::sys::*;
+use log::error;
+use rand::Rng;
+use std::collections::HashMap;
+use std::ffi::{c_char, c_void, CStr};
+use std::ptr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// What Triton reports for a traced request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceLevel {
+    /// The timeline of the request, from REQUEST_START to REQUEST_END.
+    Timestamps,
+    /// The name, type and shape of the tensors going in and out of the backend.
+    Tensors,
+    /// Both the timeline and the tensors.
+    All,
+}
+
+impl From<TraceLevel> for TRITONSERVER_InferenceTraceLevel {
+    fn from(level: TraceLevel) -> Self {
+        match level {
+            TraceLevel::Timestamps => {
+                tritonserver_tracelevel_enum_TRITONSERVER_TRACE_LEVEL_TIMESTAMPS
+            }
+            TraceLevel::Tensors => tritonserver_tracelevel_enum_TRITONSERVER_TRACE_LEVEL_TENSORS,
+            TraceLevel::All => {
+                tritonserver_tracelevel_enum_TRITONSERVER_TRACE_LEVEL_TIMESTAMPS
+                    | tritonserver_tracelevel_enum_TRITONSERVER_TRACE_LEVEL_TENSORS
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for TraceLevel {
+    type Err = RustisError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "timestamps" => Ok(TraceLevel::Timestamps),
+            "tensors" => Ok(TraceLevel::Tensors),
+            "all" => Ok(TraceLevel::All),
+            _ => Err(RustisError::Validation(format!(
+                "trace level must be 'timestamps', 'tensors' or 'all', got '{}'",
+                s
+            ))),
+        }
+    }
+}
+
+/// An activity Triton reports for a traced request.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceActivity {
+    RequestStart,
+    QueueStart,
+    ComputeStart,
+    ComputeInputEnd,
+    ComputeOutputStart,
+    ComputeEnd,
+    RequestEnd,
+    TensorQueueInput,
+    TensorBackendInput,
+    TensorBackendOutput,
+}
+
+impl TraceActivity {
+    #[allow(non_upper_case_globals)]
+    fn from_raw(activity: TRITONSERVER_InferenceTraceActivity) -> Option<Self> {
+        let activity = match activity {
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_REQUEST_START => {
+                TraceActivity::RequestStart
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_QUEUE_START => {
+                TraceActivity::QueueStart
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_COMPUTE_START => {
+                TraceActivity::ComputeStart
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_COMPUTE_INPUT_END => {
+                TraceActivity::ComputeInputEnd
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_COMPUTE_OUTPUT_START => {
+                TraceActivity::ComputeOutputStart
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_COMPUTE_END => {
+                TraceActivity::ComputeEnd
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_REQUEST_END => {
+                TraceActivity::RequestEnd
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_TENSOR_QUEUE_INPUT => {
+                TraceActivity::TensorQueueInput
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_TENSOR_BACKEND_INPUT => {
+                TraceActivity::TensorBackendInput
+            }
+            tritonserver_traceactivity_enum_TRITONSERVER_TRACE_TENSOR_BACKEND_OUTPUT => {
+                TraceActivity::TensorBackendOutput
+            }
+            _ => return None,
+        };
+        Some(activity)
+    }
+
+    /// The name Triton gives the activity, used as span event name.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TraceActivity::RequestStart => "REQUEST_START",
+            TraceActivity::QueueStart => "QUEUE_START",
+            TraceActivity::ComputeStart => "COMPUTE_START",
+            TraceActivity::ComputeInputEnd => "COMPUTE_INPUT_END",
+            TraceActivity::ComputeOutputStart => "COMPUTE_OUTPUT_START",
+            TraceActivity::ComputeEnd => "COMPUTE_END",
+            TraceActivity::RequestEnd => "REQUEST_END",
+            TraceActivity::TensorQueueInput => "TENSOR_QUEUE_INPUT",
+            TraceActivity::TensorBackendInput => "TENSOR_BACKEND_INPUT",
+            TraceActivity::TensorBackendOutput => "TENSOR_BACKEND_OUTPUT",
+        }
+    }
+}
+
+/// W3C trace context of the caller, read from its `traceparent` header.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceContext {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    /// Whether the caller records its own trace.
+    pub sampled: bool,
+}
+
+impl TraceContext {
+    /// Parses a `traceparent` header, `None` if it is malformed.
+    pub fn from_traceparent(header: &str) -> Option<Self> {
+        let fields: Vec<&str> = header.trim().split('-').collect();
+        if fields.len() < 4 {
+            return None;
+        }
+        // version ff is forbidden, later versions may append fields
+        let version = from_hex::<1>(fields[0])?;
+        if version[0] == 0xff || (version[0] == 0 && fields.len() != 4) {
+            return None;
+        }
+        let trace_id = from_hex::<16>(fields[1])?;
+        let span_id = from_hex::<8>(fields[2])?;
+        let flags = from_hex::<1>(fields[3])?;
+        if trace_id == [0; 16] || span_id == [0; 8] {
+            return None;
+        }
+        Some(TraceContext {
+            trace_id,
+            span_id,
+            sampled: flags[0] & 1 == 1,
+        })
+    }
+
+    pub fn traceparent(&self) -> String {
+        format!(
+            "00-{}-{}-{:02x}",
+            to_hex(&self.trace_id),
+            to_hex(&self.span_id),
+            self.sampled as u8
+        )
+    }
+}
+
+/// Lowercase hex encoding, as used for ids in trace headers and OTLP/JSON.
+pub fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn from_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != 2 * N || !s.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    let mut bytes = [0; N];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[2 * i..2 * i + 2], 16).ok()?;
+    }
+    Some(bytes)
+}
+
+fn random_id<const N: usize>() -> [u8; N] {
+    let mut id = [0; N];
+    // all zero ids are invalid
+    while id == [0; N] {
+        rand::thread_rng().fill(&mut id[..]);
+    }
+    id
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    String(String),
+    Int(i64),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SpanKind {
+    /// The span of a request received from a client.
+    Server,
+    Internal,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanEvent {
+    pub name: String,
+    pub time_unix_nano: u64,
+    pub attributes: Vec<(String, AttributeValue)>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Span {
+    pub trace_id: [u8; 16],
+    pub span_id: [u8; 8],
+    pub parent_span_id: Option<[u8; 8]>,
+    pub name: String,
+    pub kind: SpanKind,
+    pub start_time_unix_nano: u64,
+    pub end_time_unix_nano: u64,
+    pub attributes: Vec<(String, AttributeValue)>,
+    pub events: Vec<SpanEvent>,
+}
+
+/// Receives the spans of a traced request, or of one of the composing models
+/// of an ensemble, once Triton releases its trace. Called from Triton's
+/// threads, so it must not block.
+pub trait SpanExporter: Send + Sync {
+    fn export(&self, spans: Vec<Span>);
+
+    /// Blocks until the spans exported so far are sent, so that none are lost
+    /// when the process exits. Called on shutdown, never from Triton's threads.
+    fn flush(&self) {}
+}
+
+/// Decides which requests are traced and where their spans go.
+pub struct Tracer {
+    level: TraceLevel,
+    rate: u64,
+    count: AtomicU64,
+    exporter: Arc<dyn SpanExporter>,
+}
+
+impl Tracer {
+    /// Traces one request out of every `rate`, as well as every request
+    /// whose caller samples its own trace.
+    pub fn new(level: TraceLevel, rate: u64, exporter: Arc<dyn SpanExporter>) -> Self {
+        Tracer {
+            level,
+            rate: rate.max(1),
+            count: AtomicU64::new(0),
+            exporter,
+        }
+    }
+
+    /// Returns the trace of the next request if it is sampled. Its spans
+    /// are children of the caller's span when there is a `context`.
+    pub fn trace(
+        &self,
+        context: Option<&TraceContext>,
+    ) -> Result<Option<InferenceTrace>, TritonError> {
+        let nth = self.count.fetch_add(1, Ordering::Relaxed) % self.rate == 0;
+        if !nth && !context.map_or(false, |c| c.sampled) {
+            return Ok(None);
+        }
+        InferenceTrace::new(self.level, context, self.exporter.clone()).map(Some)
+    }
+}
+
+/// The trace of one request. Dropping it deletes the trace unless it was
+/// submitted together with its request, the release callback owns it then.
+#[derive(Debug)]
+pub struct InferenceTrace {
+    _trace: *mut TRITONSERVER_InferenceTrace,
+    collector: *mut Collector,
+}
+
+unsafe impl Send for InferenceTrace {}
+
+impl InferenceTrace {
+    pub fn new(
+        level: TraceLevel,
+        context: Option<&TraceContext>,
+        exporter: Arc<dyn SpanExporter>,
+    ) -> Result<Self, TritonError> {
+        let collector = Box::into_raw(Box::new(Collector::new(context, exporter)));
+        let mut trace = ptr::null_mut() as *mut TRITONSERVER_InferenceTrace;
+        let err = unsafe {
+            match level {
+                TraceLevel::Timestamps => TRITONSERVER_InferenceTraceNew(
+                    &mut trace,
+                    level.into(),
+                    0,
+                    Some(activity_callback),
+                    Some(release_callback),
+                    collector as *mut c_void,
+                ),
+                TraceLevel::Tensors | TraceLevel::All => TRITONSERVER_InferenceTraceTensorNew(
+                    &mut trace,
+                    level.into(),
+                    0,
+                    Some(activity_callback),
+                    Some(tensor_activity_callback),
+                    Some(release_callback),
+                    collector as *mut c_void,
+                ),
+            }
+        };
+        if !err.is_null() {
+            let _collector = unsafe { Box::from_raw(collector) };
+            return Err(TritonError::from_ptr(err));
+        }
+        Ok(InferenceTrace {
+            _trace: trace,
+            collector,
+        })
+    }
+
+    pub fn raw_pointer(&self) -> *mut TRITONSERVER_InferenceTrace {
+        self._trace
+    }
+
+    /// The W3C trace id the spans are exported with.
+    pub fn trace_id(&self) -> [u8; 16] {
+        unsafe { (*self.collector).trace_id }
+    }
+
+    /// Hands the trace over to Triton together with its request.
+    pub(crate) fn into_submitted(mut self) {
+        self._trace = ptr::null_mut();
+        self.collector = ptr::null_mut();
+    }
+}
+
+impl Drop for InferenceTrace {
+    fn drop(&mut self) {
+        if self._trace.is_null() {
+            return;
+        }
+        let err = unsafe { TRITONSERVER_InferenceTraceDelete(self._trace) };
+        if !err.is_null() {
+            error!(
+                "error deleting trace: {:?}",
+                TritonError::from_ptr(err).msg()
+            );
+        }
+        let _collector = unsafe { Box::from_raw(self.collector) };
+    }
+}
+
+// what is known of one trace, Triton spawns child traces for the composing
+// models of an ensemble
+#[derive(Debug, Default)]
+struct TraceRecord {
+    span_id: [u8; 8],
+    parent_span_id: Option<[u8; 8]>,
+    root: bool,
+    id: u64,
+    model_name: String,
+    model_version: i64,
+    activities: Vec<(TraceActivity, u64)>,
+    tensors: Vec<SpanEvent>,
+}
+
+// userp of the trace callbacks, freed when the root trace is released
+struct Collector {
+    trace_id: [u8; 16],
+    parent_span_id: Option<[u8; 8]>,
+    // Triton timestamps come from the steady clock
+    clock_offset_ns: i128,
+    exporter: Arc<dyn SpanExporter>,
+    records: Mutex<HashMap<u64, TraceRecord>>,
+}
+
+impl Collector {
+    fn new(context: Option<&TraceContext>, exporter: Arc<dyn SpanExporter>) -> Self {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_nanos() as i128;
+        Collector {
+            trace_id: context.map_or_else(random_id, |c| c.trace_id),
+            parent_span_id: context.map(|c| c.span_id),
+            clock_offset_ns: now - steady_clock_ns() as i128,
+            exporter,
+            records: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn unix_nano(&self, timestamp_ns: u64) -> u64 {
+        (timestamp_ns as i128 + self.clock_offset_ns).max(0) as u64
+    }
+
+    // calls `f` with the record of `trace`, created on its first activity
+    fn record(
+        &self,
+        trace: *mut TRITONSERVER_InferenceTrace,
+        f: impl FnOnce(&mut TraceRecord),
+    ) -> Result<(), TritonError> {
+        let (id, parent_id) = trace_ids(trace)?;
+        let mut records = self.records.lock().unwrap();
+        if !records.contains_key(&id) {
+            // the parent has started before spawning this trace
+            let parent_span_id = match parent_id {
+                0 => self.parent_span_id,
+                _ => records.get(&parent_id).map(|r| r.span_id),
+            };
+            let (model_name, model_version) = trace_model(trace)?;
+            let record = TraceRecord {
+                span_id: random_id(),
+                parent_span_id,
+                root: parent_id == 0,
+                id,
+                model_name,
+                model_version,
+                ..Default::default()
+            };
+            records.insert(id, record);
+        }
+        f(records.get_mut(&id).unwrap());
+        Ok(())
+    }
+
+    fn release(&self, id: u64) -> Vec<Span> {
+        let record = self.records.lock().unwrap().remove(&id);
+        match record {
+            Some(record) => self.spans(record),
+            None => vec![],
+        }
+    }
+
+    // the span of the request, with queue and compute spans under it
+    fn spans(&self, record: TraceRecord) -> Vec<Span> {
+        let time = |activity| {
+            record
+                .activities
+                .iter()
+                .find(|(a, _)| *a == activity)
+                .map(|(_, t)| self.unix_nano(*t))
+        };
+        let mut events: Vec<SpanEvent> = record
+            .activities
+            .iter()
+            .map(|(activity, t)| SpanEvent {
+                name: activity.as_str().to_owned(),
+                time_unix_nano: self.unix_nano(*t),
+                attributes: vec![],
+            })
+            .chain(record.tensors.iter().cloned())
+            .collect();
+        events.sort_by_key(|e| e.time_unix_nano);
+        // tensor only traces have no timeline
+        let start = time(TraceActivity::RequestStart).or(events.first().map(|e| e.time_unix_nano));
+        let end = time(TraceActivity::RequestEnd).or(events.last().map(|e| e.time_unix_nano));
+        let (start, end) = match (start, end) {
+            (Some(start), Some(end)) => (start, end),
+            _ => return vec![],
+        };
+
+        let child = |name: &str, start, end, events| Span {
+            trace_id: self.trace_id,
+            span_id: random_id(),
+            parent_span_id: Some(record.span_id),
+            name: name.to_owned(),
+            kind: SpanKind::Internal,
+            start_time_unix_nano: start,
+            end_time_unix_nano: end,
+            attributes: vec![],
+            events,
+        };
+        let mut spans = vec![];
+        if let (Some(queue), Some(compute)) = (
+            time(TraceActivity::QueueStart),
+            time(TraceActivity::ComputeStart),
+        ) {
+            spans.push(child("queue", queue, compute, vec![]));
+        }
+        if let (Some(compute_start), Some(compute_end)) = (
+            time(TraceActivity::ComputeStart),
+            time(TraceActivity::ComputeEnd),
+        ) {
+            let compute_events = events
+                .iter()
+                .filter(|e| e.name == "COMPUTE_INPUT_END" || e.name == "COMPUTE_OUTPUT_START")
+                .cloned()
+                .collect();
+            spans.push(child("compute", compute_start, compute_end, compute_events));
+        }
+        spans.insert(
+            0,
+            Span {
+                trace_id: self.trace_id,
+                span_id: record.span_id,
+                parent_span_id: record.parent_span_id,
+                name: record.model_name.clone(),
+                kind: match record.root {
+                    true => SpanKind::Server,
+                    false => SpanKind::Internal,
+                },
+                start_time_unix_nano: start,
+                end_time_unix_nano: end,
+                attributes: vec![
+                    (
+                        "triton.model_name".to_owned(),
+                        AttributeValue::String(record.model_name.clone()),
+                    ),
+                    (
+                        "triton.model_version".to_owned(),
+                        AttributeValue::Int(record.model_version),
+                    ),
+                    (
+                        "triton.trace_id".to_owned(),
+                        AttributeValue::Int(record.id as i64),
+                    ),
+                ],
+                events,
+            },
+        );
+        spans
+    }
+}
+
+fn steady_clock_ns() -> u64 {
+    let mut now = libc::timespec {
+        tv_sec: 0,
+        tv_nsec: 0,
+    };
+    unsafe { libc::clock_gettime(libc::CLOCK_MONOTONIC, &mut now) };
+    now.tv_sec as u64 * 1_000_000_000 + now.tv_nsec as u64
+}
+
+fn trace_ids(trace: *mut TRITONSERVER_InferenceTrace) -> Result<(u64, u64), TritonError> {
+    let (mut id, mut parent_id) = (0, 0);
+    let err = unsafe { TRITONSERVER_InferenceTraceId(trace, &mut id) };
+    if !err.is_null() {
+        return Err(TritonError::from_ptr(err));
+    }
+    let err = unsafe { TRITONSERVER_InferenceTraceParentId(trace, &mut parent_id) };
+    if !err.is_null() {
+        return Err(TritonError::from_ptr(err));
+    }
+    Ok((id, parent_id))
+}
+
+fn trace_model(trace: *mut TRITONSERVER_InferenceTrace) -> Result<(String, i64), TritonError> {
+    let mut model_name = ptr::null() as *const c_char;
+    let mut model_version = 0;
+    let err = unsafe { TRITONSERVER_InferenceTraceModelName(trace, &mut model_name) };
+    if !err.is_null() {
+        return Err(TritonError::from_ptr(err));
+    }
+    let err = unsafe { TRITONSERVER_InferenceTraceModelVersion(trace, &mut model_version) };
+    if !err.is_null() {
+        return Err(TritonError::from_ptr(err));
+    }
+    let model_name = unsafe { CStr::from_ptr(model_name) }
+        .to_string_lossy()
+        .into_owned();
+    Ok((model_name, model_version))
+}
+
+unsafe extern "C" fn activity_callback(
+    trace: *mut TRITONSERVER_InferenceTrace,
+    activity: TRITONSERVER_InferenceTraceActivity,
+    timestamp_ns: u64,
+    userp: *mut c_void,
+) {
+    let collector = &*(userp as *const Collector);
+    let activity = match TraceActivity::from_raw(activity) {
+        Some(activity) => activity,
+        None => return,
+    };
+    if let Err(e) = collector.record(trace, |r| r.activities.push((activity, timestamp_ns))) {
+        error!("error recording trace activity: {:?}", e.msg());
+    }
+}
+
+// the tensor contents are not exported, only their description
+unsafe extern "C" fn tensor_activity_callback(
+    trace: *mut TRITONSERVER_InferenceTrace,
+    activity: TRITONSERVER_InferenceTraceActivity,
+    name: *const c_char,
+    datatype: TRITONSERVER_DataType,
+    _base: *const c_void,
+    byte_size: usize,
+    shape: *const i64,
+    dim_count: u64,
+    _memory_type: TRITONSERVER_MemoryType,
+    _memory_type_id: i64,
+    userp: *mut c_void,
+) {
+    let collector = &*(userp as *const Collector);
+    let activity = match TraceActivity::from_raw(activity) {
+        Some(activity) => activity,
+        None => return,
+    };
+    let name = CStr::from_ptr(name).to_string_lossy().into_owned();
+    let shape = match dim_count {
+        0 => &[],
+        _ => std::slice::from_raw_parts(shape, dim_count as usize),
+    };
+    let event = SpanEvent {
+        name: activity.as_str().to_owned(),
+        // tensor activities carry no timestamp
+        time_unix_nano: collector.unix_nano(steady_clock_ns()),
+        attributes: vec![
+            ("tensor.name".to_owned(), AttributeValue::String(name)),
+            (
+                "tensor.datatype".to_owned(),
+                AttributeValue::String(utils::data_type_to_string(datatype).to_owned()),
+            ),
+            (
+                "tensor.shape".to_owned(),
+                AttributeValue::String(format!("{:?}", shape)),
+            ),
+            (
+                "tensor.byte_size".to_owned(),
+                AttributeValue::Int(byte_size as i64),
+            ),
+        ],
+    };
+    if let Err(e) = collector.record(trace, |r| r.tensors.push(event)) {
+        error!("error recording trace tensor: {:?}", e.msg());
+    }
+}
+
+unsafe extern "C" fn release_callback(trace: *mut TRITONSERVER_InferenceTrace, userp: *mut c_void) {
+    let collector = userp as *mut Collector;
+    match trace_ids(trace) {
+        Ok((id, parent_id)) => {
+            let spans = (*collector).release(id);
+            if !spans.is_empty() {
+                (*collector).exporter.export(spans);
+            }
+            let err = TRITONSERVER_InferenceTraceDelete(trace);
+            if !err.is_null() {
+                error!(
+                    "error deleting trace: {:?}",
+                    TritonError::from_ptr(err).msg()
+                );
+            }
+            // child traces are released before their parent
+            if parent_id == 0 {
+                let _collector = Box::from_raw(collector);
+            }
+        }
+        // without ids the root cannot be told apart, leak rather than double free
+        Err(e) => error!("error releasing trace: {:?}", e.msg()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct Discard;
+
+    impl SpanExporter for Discard {
+        fn export(&self, _spans: Vec<Span>) {}
+    }
+
+    #[test]
+    fn test_traceparent() {
+        let header = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+        let context = TraceContext::from_traceparent(header).unwrap();
+        assert_eq!(
+            to_hex(&context.trace_id),
+            "4bf92f3577b34da6a3ce929d0e0e4736"
+        );
+        assert_eq!(to_hex(&context.span_id), "00f067aa0ba902b7");
+        assert!(context.sampled);
+        assert_eq!(context.traceparent(), header);
+
+        for header in [
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+            "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+            "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-+0f067aa0ba902b7-01",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-ab",
+        ] {
+            assert_eq!(TraceContext::from_traceparent(header), None, "{}", header);
+        }
+        let future = "01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-ab";
+        assert!(!TraceContext::from_traceparent(future).unwrap().sampled);
+    }
+
+    #[test]
+    fn test_trace_spans() {
+        let context = TraceContext::from_traceparent(
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        )
+        .unwrap();
+        let collector = Collector::new(Some(&context), Arc::new(Discard));
+        let activities = [
+            TraceActivity::RequestStart,
+            TraceActivity::QueueStart,
+            TraceActivity::ComputeStart,
+            TraceActivity::ComputeInputEnd,
+            TraceActivity::ComputeOutputStart,
+            TraceActivity::ComputeEnd,
+            TraceActivity::RequestEnd,
+        ];
+        let record = TraceRecord {
+            span_id: random_id(),
+            parent_span_id: collector.parent_span_id,
+            root: true,
+            id: 1,
+            model_name: "add_sub".to_owned(),
+            model_version: 1,
+            activities: activities
+                .iter()
+                .enumerate()
+                .map(|(i, a)| (*a, 1_000 * i as u64))
+                .collect(),
+            tensors: vec![],
+        };
+        let span_id = record.span_id;
+        let spans = collector.spans(record);
+
+        assert_eq!(spans.len(), 3);
+        let request = &spans[0];
+        assert_eq!(request.trace_id, context.trace_id);
+        assert_eq!(request.parent_span_id, Some(context.span_id));
+        assert_eq!(request.kind, SpanKind::Server);
+        assert_eq!(request.events.len(), activities.len());
+        assert_eq!(
+            request.end_time_unix_nano - request.start_time_unix_nano,
+            6_000
+        );
+        let queue = &spans[1];
+        assert_eq!(
+            (queue.name.as_str(), queue.parent_span_id),
+            ("queue", Some(span_id))
+        );
+        assert_eq!(queue.end_time_unix_nano - queue.start_time_unix_nano, 1_000);
+        let compute = &spans[2];
+        assert_eq!(compute.name, "compute");
+        assert_eq!(
+            compute.end_time_unix_nano - compute.start_time_unix_nano,
+            3_000
+        );
+        assert_eq!(compute.events.len(), 2);
+
+        // nothing to report without any activity
+        assert!(collector.spans(TraceRecord::default()).is_empty());
+    }
+}